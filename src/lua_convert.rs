@@ -1,10 +1,12 @@
 use mlua::prelude::*;
-use std::borrow::Cow;
 use std::rc::Rc;
 use std::collections::HashMap;
+use std::convert::TryInto;
 
+use bstr::ByteSlice;
 use rxml::{NCName, CData};
 
+use crate::stanza::AttrName;
 use crate::validation;
 
 pub fn strict_string_from_lua<'a>(v: &'a LuaValue) -> LuaResult<&'a [u8]> {
@@ -18,7 +20,7 @@ pub fn strict_string_from_lua<'a>(v: &'a LuaValue) -> LuaResult<&'a [u8]> {
 
 pub fn convert_element_name_from_lua(v: LuaValue) -> LuaResult<String> {
 	let raw = strict_string_from_lua(&v)?;
-	match validation::convert_xml_element_name(Cow::from(raw)) {
+	match validation::convert_xml_element_name(raw.as_bstr()) {
 		Ok(s) => Ok(s),
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid element name: {}", e))),
 	}
@@ -30,7 +32,7 @@ pub fn convert_ncname_from_lua(v: LuaValue) -> LuaResult<NCName> {
 		Ok(s) => s,
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid utf-8: {}", e))),
 	};
-	match NCName::from_string(s) {
+	match s.try_into() {
 		Ok(s) => Ok(s),
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid ncname: {}", e))),
 	}
@@ -38,7 +40,7 @@ pub fn convert_ncname_from_lua(v: LuaValue) -> LuaResult<NCName> {
 
 pub fn convert_attribute_name_from_lua(v: LuaValue) -> LuaResult<String> {
 	let raw = strict_string_from_lua(&v)?;
-	match validation::convert_xml_attribute_name(Cow::from(raw)) {
+	match validation::convert_xml_attribute_name(raw.as_bstr()) {
 		Ok(s) => Ok(s),
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid attribute name: {}", e))),
 	}
@@ -46,7 +48,7 @@ pub fn convert_attribute_name_from_lua(v: LuaValue) -> LuaResult<String> {
 
 pub fn convert_character_data_from_lua(v: LuaValue) -> LuaResult<String> {
 	let raw = strict_string_from_lua(&v)?;
-	match validation::convert_xml_cdata(Cow::from(raw)) {
+	match validation::convert_xml_cdata(raw.as_bstr()) {
 		Ok(s) => Ok(s),
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid cdata/text: {}", e))),
 	}
@@ -58,7 +60,7 @@ pub fn convert_cdata_from_lua(v: LuaValue) -> LuaResult<CData> {
 		Ok(s) => s,
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid utf-8: {}", e))),
 	};
-	match CData::from_string(s) {
+	match s.try_into() {
 		Ok(s) => Ok(s),
 		Err(e) => return Err(LuaError::RuntimeError(format!("invalid cdata/text: {}", e))),
 	}
@@ -103,7 +105,7 @@ pub fn lua_table_to_plain_attr(tbl: LuaTable) -> LuaResult<HashMap<String, Strin
 	Ok(result)
 }
 
-pub fn lua_table_to_attr(tbl: Option<LuaTable>) -> LuaResult<(Option<Rc<CData>>, Option<HashMap<String, String>>)> {
+pub fn lua_table_to_attr(tbl: Option<LuaTable>) -> LuaResult<(Option<Rc<CData>>, Option<HashMap<AttrName, String>>)> {
 	if let Some(tbl) = tbl {
 		let mut result = HashMap::new();
 		let mut nsuri = None;
@@ -114,6 +116,10 @@ pub fn lua_table_to_attr(tbl: Option<LuaTable>) -> LuaResult<(Option<Rc<CData>>,
 				nsuri = Some(Rc::new(convert_cdata_from_lua(value)?));
 			} else {
 				let value = convert_character_data_from_lua(value)?;
+				let key = match AttrName::try_from(key.as_str()) {
+					Ok(key) => key,
+					Err(e) => return Err(LuaError::RuntimeError(format!("invalid attribute name: {}", e))),
+				};
 				result.insert(key, value);
 			}
 		}
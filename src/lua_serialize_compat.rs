@@ -1,7 +1,11 @@
 use mlua::prelude::*;
-use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::rc::Rc;
 
-use crate::tree;
+use rxml::CData;
+
+use crate::stanza::tree;
 use crate::lua_convert::*;
 
 pub trait Preserialize {
@@ -13,6 +17,75 @@ pub trait Deserialize {
 		where Self: Sized;
 }
 
+/// Failures from [`DeserializeBudget`], signalling that a preserialized
+/// table exceeded the [`DeserializeLimits`] it was checked against.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeserializeError {
+	TooDeep(usize),
+	TooManyNodes(usize),
+}
+
+impl fmt::Display for DeserializeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::TooDeep(max_depth) => write!(f, "element nesting exceeds the maximum depth of {}", max_depth),
+			Self::TooManyNodes(max_node_count) => write!(f, "node count exceeds the maximum of {}", max_node_count),
+		}
+	}
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// Guards [`deserialize_element`]/[`deserialize_node`] against
+/// maliciously deep or enormous preserialized tables (e.g. restored from
+/// storage or received cross-process), which would otherwise recurse
+/// until the native stack overflows. `max_depth` bounds element
+/// nesting; `max_node_count` bounds the total number of nodes (text and
+/// element) materialized across the whole call.
+#[derive(Debug, Clone, Copy)]
+pub struct DeserializeLimits {
+	pub max_depth: usize,
+	pub max_node_count: usize,
+}
+
+impl Default for DeserializeLimits {
+	fn default() -> Self {
+		Self {
+			max_depth: 512,
+			max_node_count: 65536,
+		}
+	}
+}
+
+/// Running counters threaded down the deserialize recursion, checked
+/// before each new node is allocated.
+struct DeserializeBudget {
+	limits: DeserializeLimits,
+	node_count: usize,
+}
+
+impl DeserializeBudget {
+	fn new(limits: DeserializeLimits) -> Self {
+		Self { limits, node_count: 0 }
+	}
+
+	fn check_depth(&self, depth: usize) -> Result<(), DeserializeError> {
+		if depth > self.limits.max_depth {
+			Err(DeserializeError::TooDeep(self.limits.max_depth))
+		} else {
+			Ok(())
+		}
+	}
+
+	fn take_node(&mut self) -> Result<(), DeserializeError> {
+		if self.node_count >= self.limits.max_node_count {
+			return Err(DeserializeError::TooManyNodes(self.limits.max_node_count));
+		}
+		self.node_count += 1;
+		Ok(())
+	}
+}
+
 impl Preserialize for tree::Node {
 	fn preserialize<'l>(&self, lua: &'l Lua) -> LuaResult<LuaValue<'l>> {
 		match self {
@@ -24,18 +97,33 @@ impl Preserialize for tree::Node {
 
 impl Deserialize for tree::Node {
 	fn deserialize<'l>(lua: &'l Lua, value: LuaValue<'l>) -> LuaResult<Self> {
-		match value {
-			LuaValue::String(s) => Ok(tree::Node::Text(s.to_str()?.to_string())),
-			other => Ok(tree::Node::Element(tree::ElementPtr::deserialize(lua, other)?)),
+		let mut budget = DeserializeBudget::new(DeserializeLimits::default());
+		deserialize_node(lua, value, None, 0, &mut budget)
+	}
+}
+
+fn deserialize_node<'l>(lua: &'l Lua, value: LuaValue<'l>, inherited_nsuri: Option<Rc<CData>>, depth: usize, budget: &mut DeserializeBudget) -> LuaResult<tree::Node> {
+	match value {
+		LuaValue::String(s) => {
+			budget.take_node().map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+			Ok(tree::Node::wrap_text(s.to_str()?.to_string()))
 		}
+		other => Ok(tree::Node::Element(deserialize_element(lua, other, inherited_nsuri, depth, budget)?)),
 	}
 }
 
 impl Preserialize for tree::Element {
 	fn preserialize<'l>(&self, lua: &'l Lua) -> LuaResult<LuaValue<'l>> {
 		let result = lua.create_table()?;
-		result.set("name", self.localname.clone())?;
-		result.set("attr", self.attr.preserialize(lua)?)?;
+		result.set("name", self.localname.as_str())?;
+		let attr = lua.create_table()?;
+		if let Some(nsuri) = self.nsuri.as_ref() {
+			attr.set("xmlns", nsuri.as_str())?;
+		}
+		for (k, v) in self.attr.iter() {
+			attr.set(k.as_ref(), v.as_str())?;
+		}
+		result.set("attr", attr)?;
 		for (i, child_node) in self.iter().enumerate() {
 			let lua_i = i + 1;
 			result.set(lua_i, child_node.preserialize(lua)?)?;
@@ -46,33 +134,53 @@ impl Preserialize for tree::Element {
 
 impl Deserialize for tree::ElementPtr {
 	fn deserialize<'l>(lua: &'l Lua, value: LuaValue<'l>) -> LuaResult<Self> {
-		let value = LuaTable::from_lua(value, lua)?;
-		let name = convert_element_name_from_lua(value.get::<_, LuaValue>("name")?)?;
-		let attr = lua_table_to_attr(value.get::<_, LuaTable>("attr")?)?;
-		let el = tree::ElementPtr::new_with_attr(
-			name,
-			Some(attr),
-		);
-		let mut numindex = 1usize;
-		{
-			let mut el_mut = el.borrow_mut();
-			loop {
-				let val = value.get::<_, LuaValue>(numindex)?;
-				if let LuaValue::Nil = val {
-					break;
-				}
-				el_mut.push(tree::Node::deserialize(lua, val)?);
-				numindex += 1;
-			}
-		}
-		Ok(el)
+		let mut budget = DeserializeBudget::new(DeserializeLimits::default());
+		deserialize_element(lua, value, None, 0, &mut budget)
 	}
 }
 
-impl Preserialize for HashMap<String, String> {
-	fn preserialize<'l>(&self, lua: &'l Lua) -> LuaResult<LuaValue<'l>> {
-		self.clone().to_lua(lua)
+/// Deserialize the root of a preserialize table, enforcing `limits`
+/// against the whole resulting tree. Exposed for callers (e.g.
+/// [`crate::stanza::lua::stanza_deserialize`]) that want to override the
+/// [`DeserializeLimits::default`] depth/node-count guards.
+pub fn deserialize_root<'l>(lua: &'l Lua, value: LuaValue<'l>, limits: DeserializeLimits) -> LuaResult<tree::ElementPtr> {
+	let mut budget = DeserializeBudget::new(limits);
+	deserialize_element(lua, value, None, 0, &mut budget)
+}
+
+/// Deserialize an element, resolving its namespace from `attr.xmlns` if
+/// present, falling back to `inherited_nsuri` (the enclosing element's
+/// namespace) otherwise -- mirroring how a missing `xmlns` on the wire
+/// means "same namespace as my parent". `depth` is the nesting level of
+/// `value` itself (the root is depth 0); checked against `budget` before
+/// any child is allocated so a too-deep or too-large tree aborts rather
+/// than recursing to a stack overflow.
+fn deserialize_element<'l>(lua: &'l Lua, value: LuaValue<'l>, inherited_nsuri: Option<Rc<CData>>, depth: usize, budget: &mut DeserializeBudget) -> LuaResult<tree::ElementPtr> {
+	budget.check_depth(depth).map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+	budget.take_node().map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+	let value = LuaTable::from_lua(value, lua)?;
+	let name = convert_element_name_from_lua(value.get::<_, LuaValue>("name")?)?;
+	let name: rxml::Name = match name.try_into() {
+		Ok(name) => name,
+		Err(e) => return Err(LuaError::RuntimeError(format!("invalid element name: {}", e))),
+	};
+	let (explicit_nsuri, attr) = lua_table_to_attr(value.get::<_, Option<LuaTable>>("attr")?)?;
+	let nsuri = explicit_nsuri.or(inherited_nsuri);
+	let el = tree::ElementPtr::new_with_attr(nsuri.clone(), name, attr);
+	let mut numindex = 1usize;
+	{
+		let mut el_mut = el.borrow_mut();
+		loop {
+			let val = value.get::<_, LuaValue>(numindex)?;
+			if let LuaValue::Nil = val {
+				break;
+			}
+			let child = deserialize_node(lua, val, nsuri.clone(), depth + 1, budget)?;
+			el_mut.push(child).map_err(|e| LuaError::RuntimeError(e.to_string()))?;
+			numindex += 1;
+		}
 	}
+	Ok(el)
 }
 
 impl Preserialize for String {
@@ -151,6 +151,40 @@ impl AttrName {
 		}
 	}
 
+	/// The inverse of the `prosody-tmp-ns`/prefix-reuse emission path in
+	/// `xml::Formatter`: resolve a raw `prefix:local` (or unprefixed
+	/// `local`) attribute name as seen on the wire into an `AttrName`,
+	/// looking up `prefix` via `lookup` against the namespace bindings
+	/// in scope at that point in the document. `xml:`/`xmlns:`-prefixed
+	/// names are handled the same way [`from_str`](Self::from_str) always
+	/// has, without consulting `lookup`.
+	pub fn resolve<F: Fn(&str) -> Option<&CDataStr>>(qname: &str, lookup: F) -> Result<AttrName, &'static str> {
+		if qname.starts_with("xml:") || qname.starts_with("xmlns:") {
+			return Self::from_str(qname);
+		}
+		match qname.find(':') {
+			Some(first_colon) => {
+				let prefix = &qname[..first_colon];
+				let localname = &qname[first_colon+1..];
+				let localname = match NCNameStr::from_str(localname) {
+					Ok(v) => v,
+					Err(_) => return Err("local name is not well formed"),
+				};
+				match lookup(prefix) {
+					Some(uri) => Ok(Self::compose(Some(uri), localname)),
+					None => Err("namespace prefix is not bound"),
+				}
+			},
+			None => {
+				let localname = match NCNameStr::from_str(qname) {
+					Ok(v) => v,
+					Err(_) => return Err("local name is not well formed"),
+				};
+				Ok(Self::local_only(localname))
+			},
+		}
+	}
+
 	pub unsafe fn from_str_unsafe<T: AsRef<str>>(s: T) -> AttrName {
 		AttrName(s.as_ref().into())
 	}
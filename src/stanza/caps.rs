@@ -0,0 +1,248 @@
+//! [XEP-0115](https://xmpp.org/extensions/xep-0115.html) Entity
+//! Capabilities verification-string computation over a disco#info reply.
+use std::cell::Ref;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fmt;
+
+use base64::Engine as _;
+
+use super::attrstr::AttrName;
+use super::tree;
+
+pub const XMLNS_DISCO_INFO: &str = "http://jabber.org/protocol/disco#info";
+pub const XMLNS_DATA_FORMS: &str = "jabber:x:data";
+
+/// Hash algorithms [`compute_verification_string`] can use to digest the
+/// generation-method-`S` byte string into a "ver" string.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HashAlgo {
+	Sha1,
+	Sha256,
+	Blake2b,
+}
+
+impl HashAlgo {
+	fn digest(&self, data: &[u8]) -> Vec<u8> {
+		match self {
+			Self::Sha1 => {
+				use sha1::{Sha1, Digest};
+				Sha1::digest(data).to_vec()
+			},
+			Self::Sha256 => {
+				use sha2::{Sha256, Digest};
+				Sha256::digest(data).to_vec()
+			},
+			Self::Blake2b => {
+				use blake2::{Blake2b512, Digest};
+				Blake2b512::digest(data).to_vec()
+			},
+		}
+	}
+}
+
+/// Failures from [`compute_verification_string`].
+///
+/// Per XEP-0115 §8.4, a disco#info reply carrying duplicate identities or
+/// duplicate features must be rejected outright rather than hashed, since
+/// a forged duplicate could otherwise be used to poison the cache under a
+/// colliding "ver" string.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CapsError {
+	DuplicateIdentity,
+	DuplicateFeature,
+}
+
+impl fmt::Display for CapsError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::DuplicateIdentity => write!(f, "duplicate <identity/> in disco#info query"),
+			Self::DuplicateFeature => write!(f, "duplicate <feature/> in disco#info query"),
+		}
+	}
+}
+
+impl std::error::Error for CapsError {}
+
+fn lang_key() -> AttrName {
+	"xml:lang".try_into().unwrap()
+}
+
+fn attr_or_empty(el: &tree::Element, key: &str) -> String {
+	el.attr.get(key).cloned().unwrap_or_default()
+}
+
+fn direct_children<'a>(el: Ref<'a, tree::Element>, name: &str) -> Vec<tree::ElementPtr> {
+	super::xmpp::ElementSelector::select_inside_parent(Ref::clone(&el), Some(name.to_string()), None)
+		.find_all(el.iter_children())
+}
+
+fn namespaced_children<'a>(el: Ref<'a, tree::Element>, name: &str, xmlns: &str) -> Vec<tree::ElementPtr> {
+	let nsuri = Some(std::rc::Rc::new(rxml::CData::from_string(xmlns.to_string()).unwrap()));
+	super::xmpp::ElementSelector::select_inside_xmlns(el.nsuri.clone(), Some(name.to_string()), nsuri)
+		.find_all(el.iter_children())
+}
+
+/// Compute the XEP-0115 "ver" string for a `<query
+/// xmlns='http://jabber.org/protocol/disco#info'/>` element: the
+/// generation-method-`S` byte string, hashed with `hash` and
+/// Base64-encoded.
+pub fn compute_verification_string<'a>(query: Ref<'a, tree::Element>, hash: HashAlgo) -> Result<String, CapsError> {
+	let mut identities: Vec<(String, String, String, String)> = Vec::new();
+	let mut seen_identities: HashSet<(String, String, String)> = HashSet::new();
+	for el_ptr in direct_children(Ref::clone(&query), "identity") {
+		let el = el_ptr.borrow();
+		let category = attr_or_empty(&el, "category");
+		let type_ = attr_or_empty(&el, "type");
+		let lang = el.attr.get(&lang_key()).cloned().unwrap_or_default();
+		let name = attr_or_empty(&el, "name");
+		if !seen_identities.insert((category.clone(), type_.clone(), lang.clone())) {
+			return Err(CapsError::DuplicateIdentity);
+		}
+		identities.push((category, type_, lang, name));
+	}
+	identities.sort();
+
+	let mut features: Vec<String> = Vec::new();
+	let mut seen_features: HashSet<String> = HashSet::new();
+	for el_ptr in direct_children(Ref::clone(&query), "feature") {
+		let var = attr_or_empty(&el_ptr.borrow(), "var");
+		if !seen_features.insert(var.clone()) {
+			return Err(CapsError::DuplicateFeature);
+		}
+		features.push(var);
+	}
+	features.sort();
+
+	// (FORM_TYPE value, sorted remaining (var, sorted values) pairs)
+	let mut forms: Vec<(String, Vec<(String, Vec<String>)>)> = Vec::new();
+	for x_ptr in namespaced_children(Ref::clone(&query), "x", XMLNS_DATA_FORMS) {
+		let x = x_ptr.borrow();
+		let mut form_type = String::new();
+		let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+		for field_ptr in direct_children(Ref::clone(&x), "field") {
+			let field = field_ptr.borrow();
+			let var = attr_or_empty(&field, "var");
+			let mut values: Vec<String> = Vec::new();
+			for value_ptr in direct_children(Ref::clone(&field), "value") {
+				if let Some(text) = value_ptr.borrow().get_text() {
+					values.push(text);
+				}
+			}
+			values.sort();
+			if var == "FORM_TYPE" {
+				form_type = values.first().cloned().unwrap_or_default();
+			} else {
+				fields.push((var, values));
+			}
+		}
+		fields.sort_by(|a, b| a.0.cmp(&b.0));
+		forms.push((form_type, fields));
+	}
+	forms.sort_by(|a, b| a.0.cmp(&b.0));
+
+	let mut s = String::new();
+	for (category, type_, lang, name) in identities.iter() {
+		s.push_str(category);
+		s.push('/');
+		s.push_str(type_);
+		s.push('/');
+		s.push_str(lang);
+		s.push('/');
+		s.push_str(name);
+		s.push('<');
+	}
+	for feature in features.iter() {
+		s.push_str(feature);
+		s.push('<');
+	}
+	for (form_type, fields) in forms.iter() {
+		s.push_str(form_type);
+		s.push('<');
+		for (var, values) in fields.iter() {
+			s.push_str(var);
+			s.push('<');
+			for value in values.iter() {
+				s.push_str(value);
+				s.push('<');
+			}
+		}
+	}
+
+	let digest = hash.digest(s.as_bytes());
+	Ok(base64::engine::general_purpose::STANDARD.encode(&digest))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::convert::TryInto;
+
+	fn mkquery() -> tree::ElementPtr {
+		tree::ElementPtr::new(
+			Some(std::rc::Rc::new(rxml::CData::from_string(XMLNS_DISCO_INFO.to_string()).unwrap())),
+			"query".try_into().unwrap(),
+		)
+	}
+
+	fn add_identity(query: &tree::ElementPtr, category: &str, type_: &str, name: &str) {
+		let mut attr = std::collections::HashMap::new();
+		attr.insert("category".try_into().unwrap(), category.to_string());
+		attr.insert("type".try_into().unwrap(), type_.to_string());
+		attr.insert("name".try_into().unwrap(), name.to_string());
+		query.borrow_mut().tag(None, "identity".try_into().unwrap(), Some(attr));
+	}
+
+	fn add_feature(query: &tree::ElementPtr, var: &str) {
+		let mut attr = std::collections::HashMap::new();
+		attr.insert("var".try_into().unwrap(), var.to_string());
+		query.borrow_mut().tag(None, "feature".try_into().unwrap(), Some(attr));
+	}
+
+	// The canonical "simple example" from XEP-0115 §5.1: one identity, four
+	// features, no lang or data forms.
+	#[test]
+	fn compute_verification_string_matches_xep0115_simple_example() {
+		let query = mkquery();
+		add_identity(&query, "client", "pc", "Psi 0.11");
+		add_feature(&query, "http://jabber.org/protocol/caps");
+		add_feature(&query, "http://jabber.org/protocol/disco#info");
+		add_feature(&query, "http://jabber.org/protocol/disco#items");
+		add_feature(&query, "http://jabber.org/protocol/muc");
+
+		let ver = compute_verification_string(query.borrow(), HashAlgo::Sha1).unwrap();
+		assert_eq!(ver, "joMrPam9nYuKMTFa4WETBGeEnDA=");
+	}
+
+	#[test]
+	fn compute_verification_string_sorts_features_regardless_of_input_order() {
+		let query = mkquery();
+		add_feature(&query, "http://jabber.org/protocol/muc");
+		add_feature(&query, "http://jabber.org/protocol/caps");
+		add_feature(&query, "http://jabber.org/protocol/disco#items");
+		add_feature(&query, "http://jabber.org/protocol/disco#info");
+		add_identity(&query, "client", "pc", "Psi 0.11");
+
+		let ver = compute_verification_string(query.borrow(), HashAlgo::Sha1).unwrap();
+		assert_eq!(ver, "joMrPam9nYuKMTFa4WETBGeEnDA=");
+	}
+
+	#[test]
+	fn compute_verification_string_rejects_duplicate_identity() {
+		let query = mkquery();
+		add_identity(&query, "client", "pc", "Psi 0.11");
+		add_identity(&query, "client", "pc", "Psi 0.11 (again)");
+
+		assert_eq!(compute_verification_string(query.borrow(), HashAlgo::Sha1), Err(CapsError::DuplicateIdentity));
+	}
+
+	#[test]
+	fn compute_verification_string_rejects_duplicate_feature() {
+		let query = mkquery();
+		add_identity(&query, "client", "pc", "Psi 0.11");
+		add_feature(&query, "http://jabber.org/protocol/caps");
+		add_feature(&query, "http://jabber.org/protocol/caps");
+
+		assert_eq!(compute_verification_string(query.borrow(), HashAlgo::Sha1), Err(CapsError::DuplicateFeature));
+	}
+}
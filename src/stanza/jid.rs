@@ -0,0 +1,194 @@
+//! Parsing and normalization of XMPP addresses ([RFC 7622](https://www.rfc-editor.org/rfc/rfc7622)).
+use std::fmt;
+use std::str::FromStr;
+use std::convert::TryFrom;
+
+/// Failures from [`Jid::parse`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum JidError {
+	Empty,
+	EmptyNode,
+	EmptyDomain,
+	EmptyResource,
+	InvalidNode(String),
+	InvalidDomain(String),
+}
+
+impl fmt::Display for JidError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Empty => write!(f, "jid is empty"),
+			Self::EmptyNode => write!(f, "localpart is present but empty"),
+			Self::EmptyDomain => write!(f, "domainpart is empty"),
+			Self::EmptyResource => write!(f, "resourcepart is present but empty"),
+			Self::InvalidNode(s) => write!(f, "localpart '{}' contains a forbidden character", s),
+			Self::InvalidDomain(s) => write!(f, "domainpart '{}' contains a forbidden character", s),
+		}
+	}
+}
+
+impl std::error::Error for JidError {}
+
+fn is_forbidden_node_char(c: char) -> bool {
+	matches!(c, '"' | '&' | '\'' | '/' | ':' | '<' | '>' | '@') || c.is_whitespace() || c.is_control()
+}
+
+/// Approximate [PRECIS](https://www.rfc-editor.org/rfc/rfc8264) case-folding
+/// for a domainpart: this crate does not vendor a full IDNA/stringprep
+/// implementation, so normalization is limited to ASCII lowercasing, which
+/// is sufficient for the plain-ASCII domains this crate deals with.
+fn normalize_domain(s: &str) -> String {
+	s.to_ascii_lowercase()
+}
+
+/// A parsed and normalized XMPP address: `[ node "@" ] domain [ "/" resource ]`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Jid {
+	pub node: Option<String>,
+	pub domain: String,
+	pub resource: Option<String>,
+}
+
+impl Jid {
+	/// Parse and normalize an XMPP address, rejecting empty parts and
+	/// localparts containing characters forbidden by
+	/// [RFC 7622 §3.3.1](https://www.rfc-editor.org/rfc/rfc7622#section-3.3.1).
+	pub fn parse(s: &str) -> Result<Jid, JidError> {
+		if s.is_empty() {
+			return Err(JidError::Empty);
+		}
+
+		let (left, resource) = match s.find('/') {
+			Some(idx) => {
+				let resource = &s[idx + 1..];
+				if resource.is_empty() {
+					return Err(JidError::EmptyResource);
+				}
+				(&s[..idx], Some(resource.to_string()))
+			},
+			None => (s, None),
+		};
+
+		let (node, domain) = match left.find('@') {
+			Some(idx) => {
+				let node = &left[..idx];
+				if node.is_empty() {
+					return Err(JidError::EmptyNode);
+				}
+				if node.chars().any(is_forbidden_node_char) {
+					return Err(JidError::InvalidNode(node.to_string()));
+				}
+				(Some(node.to_string()), &left[idx + 1..])
+			},
+			None => (None, left),
+		};
+
+		if domain.is_empty() {
+			return Err(JidError::EmptyDomain);
+		}
+		if domain.chars().any(|c| c.is_whitespace() || c.is_control()) {
+			return Err(JidError::InvalidDomain(domain.to_string()));
+		}
+
+		Ok(Jid{ node, domain: normalize_domain(domain), resource })
+	}
+
+	/// Whether this address has no resourcepart.
+	pub fn is_bare(&self) -> bool {
+		self.resource.is_none()
+	}
+
+	/// This address with the resourcepart, if any, dropped.
+	pub fn bare(&self) -> Jid {
+		Jid{ node: self.node.clone(), domain: self.domain.clone(), resource: None }
+	}
+}
+
+impl fmt::Display for Jid {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if let Some(node) = self.node.as_ref() {
+			write!(f, "{}@", node)?;
+		}
+		write!(f, "{}", self.domain)?;
+		if let Some(resource) = self.resource.as_ref() {
+			write!(f, "/{}", resource)?;
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for Jid {
+	type Err = JidError;
+
+	fn from_str(s: &str) -> Result<Jid, JidError> {
+		Jid::parse(s)
+	}
+}
+
+impl TryFrom<&str> for Jid {
+	type Error = JidError;
+
+	fn try_from(s: &str) -> Result<Jid, JidError> {
+		Jid::parse(s)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_full_jid() {
+		let jid = Jid::parse("Romeo@Montague.lit/orchard").unwrap();
+		assert_eq!(jid.node.as_deref(), Some("Romeo"));
+		assert_eq!(jid.domain, "montague.lit");
+		assert_eq!(jid.resource.as_deref(), Some("orchard"));
+	}
+
+	#[test]
+	fn parses_bare_jid() {
+		let jid = Jid::parse("montague.lit").unwrap();
+		assert_eq!(jid.node, None);
+		assert_eq!(jid.domain, "montague.lit");
+		assert_eq!(jid.resource, None);
+		assert!(jid.is_bare());
+	}
+
+	#[test]
+	fn bare_drops_resource() {
+		let jid = Jid::parse("romeo@montague.lit/orchard").unwrap();
+		assert_eq!(jid.bare(), Jid::parse("romeo@montague.lit").unwrap());
+	}
+
+	#[test]
+	fn resource_may_contain_at_and_slash() {
+		let jid = Jid::parse("romeo@montague.lit/orchard/balcony@night").unwrap();
+		assert_eq!(jid.resource.as_deref(), Some("orchard/balcony@night"));
+	}
+
+	#[test]
+	fn rejects_empty_localpart() {
+		assert_eq!(Jid::parse("@montague.lit"), Err(JidError::EmptyNode));
+	}
+
+	#[test]
+	fn rejects_empty_domain() {
+		assert_eq!(Jid::parse("romeo@"), Err(JidError::EmptyDomain));
+	}
+
+	#[test]
+	fn rejects_empty_resource() {
+		assert_eq!(Jid::parse("romeo@montague.lit/"), Err(JidError::EmptyResource));
+	}
+
+	#[test]
+	fn rejects_forbidden_localpart_character() {
+		assert_eq!(Jid::parse("ro meo@montague.lit"), Err(JidError::InvalidNode("ro meo".to_string())));
+	}
+
+	#[test]
+	fn display_round_trips() {
+		let jid = Jid::parse("romeo@montague.lit/orchard").unwrap();
+		assert_eq!(jid.to_string(), "romeo@montague.lit/orchard");
+	}
+}
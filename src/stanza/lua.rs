@@ -0,0 +1,179 @@
+use std::convert::TryInto;
+
+use mlua::prelude::*;
+
+use crate::lua_convert::*;
+use crate::lua_serialize_compat::{deserialize_root, DeserializeLimits, Preserialize};
+
+use super::stanza::Stanza;
+use super::xmpp;
+
+/// `LuaUserData` wrapper around [`Stanza`], exposing the chainable
+/// `:tag()`/`:text()`/`:up()` mutation API Prosody's `util.stanza` objects
+/// are built around.
+pub struct LuaStanza(Stanza);
+
+impl LuaStanza {
+	pub fn wrap(st: Stanza) -> LuaStanza {
+		LuaStanza(st)
+	}
+}
+
+impl LuaUserData for LuaStanza {
+	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+		methods.add_function("tag", |_: &'lua Lua, (this, name, attr): (LuaAnyUserData, LuaValue, Option<LuaTable>)| -> LuaResult<LuaAnyUserData> {
+			let name = convert_element_name_from_lua(name)?;
+			let name: rxml::Name = match name.try_into() {
+				Ok(name) => name,
+				Err(e) => return Err(LuaError::RuntimeError(format!("invalid element name: {}", e))),
+			};
+			let (nsuri, attr) = lua_table_to_attr(attr)?;
+			{
+				let mut st = this.borrow_mut::<LuaStanza>()?;
+				st.0.tag(nsuri, name, attr);
+			}
+			Ok(this)
+		});
+
+		methods.add_function("text", |_: &'lua Lua, (this, data): (LuaAnyUserData, LuaValue)| -> LuaResult<LuaAnyUserData> {
+			let data = convert_character_data_from_lua(data)?;
+			{
+				let mut st = this.borrow_mut::<LuaStanza>()?;
+				st.0.text(data);
+			}
+			Ok(this)
+		});
+
+		methods.add_function("up", |_: &'lua Lua, this: LuaAnyUserData| -> LuaResult<LuaAnyUserData> {
+			{
+				let mut st = this.borrow_mut::<LuaStanza>()?;
+				st.0.up();
+			}
+			Ok(this)
+		});
+
+		methods.add_function("reset", |_: &'lua Lua, this: LuaAnyUserData| -> LuaResult<LuaAnyUserData> {
+			{
+				let mut st = this.borrow_mut::<LuaStanza>()?;
+				st.0.reset();
+			}
+			Ok(this)
+		});
+
+		methods.add_method("is_at_top", |_, this: &LuaStanza, _: ()| -> LuaResult<bool> {
+			Ok(this.0.is_at_top())
+		});
+
+		methods.add_method("name", |_, this: &LuaStanza, _: ()| -> LuaResult<Option<String>> {
+			Ok(this.0.try_deref().map(|el| el.borrow().localname.as_str().to_string()))
+		});
+
+		methods.add_method("get_attr", |_, this: &LuaStanza, key: LuaValue| -> LuaResult<Option<String>> {
+			let key = convert_attribute_name_from_lua(key)?;
+			Ok(this.0.try_deref().and_then(|el| el.borrow().attr.get(key.as_str()).cloned()))
+		});
+
+		methods.add_function("set_attr", |_: &'lua Lua, (this, key, value): (LuaAnyUserData, LuaValue, String)| -> LuaResult<LuaAnyUserData> {
+			let key = convert_attribute_name_from_lua(key)?;
+			{
+				let st = this.borrow::<LuaStanza>()?;
+				let el = st.0.try_deref().ok_or_else(|| LuaError::RuntimeError("stanza cursor is not positioned on an element".to_string()))?;
+				el.borrow_mut().attr.insert(key.as_str().try_into().unwrap(), value);
+			}
+			Ok(this)
+		});
+
+		methods.add_method("pretty_print", |_, this: &LuaStanza, _: ()| -> LuaResult<String> {
+			Ok(this.0.root_ptr().pretty_print(&super::pretty::PrettyPrintOptions::default()))
+		});
+
+		methods.add_meta_method(LuaMetaMethod::ToString, |_, this: &LuaStanza, _: ()| -> LuaResult<String> {
+			Ok(this.0.root_ptr().pretty_print(&super::pretty::PrettyPrintOptions::default()))
+		});
+	}
+}
+
+fn element_name_from_lua(v: LuaValue) -> LuaResult<rxml::Name> {
+	let name = convert_element_name_from_lua(v)?;
+	match name.try_into() {
+		Ok(name) => Ok(name),
+		Err(e) => Err(LuaError::RuntimeError(format!("invalid element name: {}", e))),
+	}
+}
+
+pub fn stanza_new<'l>(_l: &'l Lua, (name, attr): (LuaValue, Option<LuaTable>)) -> LuaResult<LuaStanza> {
+	let name = element_name_from_lua(name)?;
+	let (nsuri, attr) = lua_table_to_attr(attr)?;
+	Ok(LuaStanza::wrap(Stanza::new(nsuri, name, attr)))
+}
+
+pub fn stanza_message<'l>(_l: &'l Lua, (attr, body): (Option<LuaTable>, Option<String>)) -> LuaResult<LuaStanza> {
+	let (nsuri, attr) = lua_table_to_attr(attr)?;
+	let mut st = Stanza::new(nsuri, "message".try_into().unwrap(), attr);
+	if let Some(body) = body {
+		st.tag(None, "body".try_into().unwrap(), None);
+		st.text(body);
+		st.up();
+	}
+	Ok(LuaStanza::wrap(st))
+}
+
+pub fn stanza_iq<'l>(_l: &'l Lua, attr: Option<LuaTable>) -> LuaResult<LuaStanza> {
+	let (nsuri, attr) = lua_table_to_attr(attr)?;
+	Ok(LuaStanza::wrap(Stanza::new(nsuri, "iq".try_into().unwrap(), attr)))
+}
+
+pub fn stanza_presence<'l>(_l: &'l Lua, attr: Option<LuaTable>) -> LuaResult<LuaStanza> {
+	let (nsuri, attr) = lua_table_to_attr(attr)?;
+	Ok(LuaStanza::wrap(Stanza::new(nsuri, "presence".try_into().unwrap(), attr)))
+}
+
+pub fn stanza_reply<'l>(_l: &'l Lua, orig: LuaAnyUserData<'l>) -> LuaResult<LuaStanza> {
+	let orig = orig.borrow::<LuaStanza>()?;
+	let reply_ptr = xmpp::make_reply(orig.0.root());
+	Ok(LuaStanza::wrap(Stanza::wrap(reply_ptr)))
+}
+
+pub fn stanza_error_reply<'l>(_l: &'l Lua, (orig, type_, condition, text, by, legacy_code): (LuaAnyUserData<'l>, Option<String>, String, Option<String>, Option<String>, Option<bool>)) -> LuaResult<LuaStanza> {
+	let type_ = match type_ {
+		Some(ref s) => match xmpp::ErrorType::from_str(s) {
+			Some(t) => Some(t),
+			None => return Err(LuaError::RuntimeError(format!("invalid error type: {}", s))),
+		},
+		None => None,
+	};
+	let condition_name = match rxml::NameStr::from_str(condition.as_str()) {
+		Ok(name) => name,
+		Err(e) => return Err(LuaError::RuntimeError(format!("invalid error condition: {}", e))),
+	};
+	let condition = xmpp::Condition::from_localname(condition_name);
+
+	let orig = orig.borrow::<LuaStanza>()?;
+	match xmpp::make_error_reply(orig.0.root(), type_, condition, text, by, legacy_code.unwrap_or(false)) {
+		Ok(reply_ptr) => Ok(LuaStanza::wrap(Stanza::wrap(reply_ptr))),
+		Err(e) => Err(LuaError::RuntimeError(e)),
+	}
+}
+
+pub fn stanza_test<'l>(_l: &'l Lua, v: LuaValue<'l>) -> LuaResult<bool> {
+	Ok(match v {
+		LuaValue::UserData(ud) => ud.is::<LuaStanza>(),
+		_ => false,
+	})
+}
+
+pub fn stanza_clone<'l>(_l: &'l Lua, orig: LuaAnyUserData<'l>) -> LuaResult<LuaStanza> {
+	let orig = orig.borrow::<LuaStanza>()?;
+	Ok(LuaStanza::wrap(orig.0.deep_clone()))
+}
+
+pub fn stanza_preserialize<'l>(l: &'l Lua, orig: LuaAnyUserData<'l>) -> LuaResult<LuaValue<'l>> {
+	let orig = orig.borrow::<LuaStanza>()?;
+	let root = orig.0.root();
+	root.preserialize(l)
+}
+
+pub fn stanza_deserialize<'l>(l: &'l Lua, value: LuaValue<'l>) -> LuaResult<LuaStanza> {
+	let root_ptr = deserialize_root(l, value, DeserializeLimits::default())?;
+	Ok(LuaStanza::wrap(Stanza::wrap(root_ptr)))
+}
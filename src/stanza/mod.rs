@@ -1,11 +1,19 @@
+pub(crate) mod caps;
+pub(crate) mod jid;
 pub(crate) mod lua;
+pub(crate) mod payload;
 pub(crate) mod stanza;
 pub(crate) mod tree;
 pub(crate) mod xmpp;
 pub(crate) mod fake_xpath;
 pub(crate) mod xml;
+pub(crate) mod pp;
 pub(crate) mod path;
 pub(crate) mod attrstr;
+pub(crate) mod pretty;
+pub(crate) mod router;
+pub(crate) mod walk;
 
 pub use stanza::{Stanza};
 pub use attrstr::{AttrName};
+pub use jid::{Jid};
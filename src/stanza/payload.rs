@@ -0,0 +1,390 @@
+//! Declarative typed-payload framework, in the spirit of the
+//! `xmpp-parsers` `generate_element!`/`generate_empty_element!` macro
+//! family, built directly on this crate's own [`tree::ElementPtr`],
+//! [`ElementSelector`](super::xmpp::ElementSelector), and
+//! [`AttrName`](super::attrstr::AttrName).
+//!
+//! [`payload_element!`] declares a struct together with
+//! [`TryFromElement`]/[`IntoElement`] impls that round-trip it to and
+//! from a single named element in a given namespace. Supported field
+//! kinds, one per struct field:
+//!
+//! - `attribute(name: String) = "wire-name"` -- a required attribute.
+//! - `optional_attribute(name: String) = "wire-name"` -- an `Option<T>` attribute.
+//! - `text(name: String)` -- the element's own character data.
+//! - `child(name: Type)` -- a single required typed child.
+//! - `optional_child(name: Type)` -- a single optional typed child.
+//! - `children(name: Type)` -- every matching typed child, as a `Vec<Type>`.
+//!
+//! Unless the `disable-validation` feature is enabled, `try_from_element`
+//! rejects any attribute or child element not named by the invocation,
+//! per the usual "be strict about what you accept" stance for a stanza
+//! parser.
+use std::cell::Ref;
+use std::fmt;
+use std::rc::Rc;
+
+use rxml::CData;
+
+use super::tree;
+
+/// Failure modes for [`TryFromElement::try_from_element`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PayloadError {
+	WrongName{ expected: &'static str, got: String },
+	WrongNamespace{ expected: &'static str, got: Option<String> },
+	MissingAttribute(&'static str),
+	MissingChild(&'static str),
+	UnknownAttribute(String),
+	UnknownChild(String),
+}
+
+impl fmt::Display for PayloadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::WrongName{expected, got} => write!(f, "expected element named '{}', got '{}'", expected, got),
+			Self::WrongNamespace{expected, got: Some(got)} => write!(f, "expected element in namespace '{}', got '{}'", expected, got),
+			Self::WrongNamespace{expected, got: None} => write!(f, "expected element in namespace '{}', got no namespace", expected),
+			Self::MissingAttribute(name) => write!(f, "missing required attribute '{}'", name),
+			Self::MissingChild(name) => write!(f, "missing required child element '{}'", name),
+			Self::UnknownAttribute(name) => write!(f, "unknown attribute '{}'", name),
+			Self::UnknownChild(name) => write!(f, "unknown child element '{}'", name),
+		}
+	}
+}
+
+impl std::error::Error for PayloadError {}
+
+/// A type which can be extracted from a [`tree::ElementPtr`].
+///
+/// `LOCALNAME`/`XMLNS` identify the wire element this type maps to; they
+/// are what [`payload_element!`] uses to look up typed `child`/`children`
+/// fields by element name without the caller having to repeat it.
+pub trait TryFromElement: Sized {
+	const LOCALNAME: &'static str;
+	const XMLNS: &'static str;
+
+	fn try_from_element(el: tree::ElementPtr) -> Result<Self, PayloadError>;
+}
+
+/// The inverse of [`TryFromElement`]: serialize `self` back into a fresh
+/// [`tree::ElementPtr`].
+pub trait IntoElement {
+	fn into_element(self) -> tree::ElementPtr;
+}
+
+#[doc(hidden)]
+pub fn xmlns_rc(xmlns: &str) -> Rc<CData> {
+	Rc::new(CData::from_string(xmlns.to_string()).unwrap())
+}
+
+#[doc(hidden)]
+pub fn check_name_and_namespace(el: &Ref<tree::Element>, localname: &'static str, xmlns: &'static str) -> Result<(), PayloadError> {
+	if el.localname != localname {
+		return Err(PayloadError::WrongName{ expected: localname, got: el.localname.as_str().to_string() });
+	}
+	match el.nsuri.as_ref() {
+		Some(ns) if **ns == xmlns => Ok(()),
+		Some(ns) => Err(PayloadError::WrongNamespace{ expected: xmlns, got: Some(ns.as_str().to_string()) }),
+		None => Err(PayloadError::WrongNamespace{ expected: xmlns, got: None }),
+	}
+}
+
+#[cfg(not(feature = "disable-validation"))]
+#[doc(hidden)]
+pub fn check_known_attributes(el: &Ref<tree::Element>, known: &[&str]) -> Result<(), PayloadError> {
+	'outer: for key in el.attr.keys() {
+		let key: &str = key.as_ref();
+		for k in known {
+			if *k == key {
+				continue 'outer;
+			}
+		}
+		return Err(PayloadError::UnknownAttribute(key.to_string()));
+	}
+	Ok(())
+}
+
+#[cfg(feature = "disable-validation")]
+#[doc(hidden)]
+pub fn check_known_attributes(_el: &Ref<tree::Element>, _known: &[&str]) -> Result<(), PayloadError> {
+	Ok(())
+}
+
+#[cfg(not(feature = "disable-validation"))]
+#[doc(hidden)]
+pub fn check_known_children(el: &Ref<tree::Element>, known: &[&str]) -> Result<(), PayloadError> {
+	for child_ptr in el.iter_children() {
+		let child = child_ptr.borrow();
+		let localname: &str = child.localname.as_str();
+		if !known.iter().any(|k| *k == localname) {
+			return Err(PayloadError::UnknownChild(localname.to_string()));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(feature = "disable-validation")]
+#[doc(hidden)]
+pub fn check_known_children(_el: &Ref<tree::Element>, _known: &[&str]) -> Result<(), PayloadError> {
+	Ok(())
+}
+
+macro_rules! payload_element {
+	(
+		$(#[$meta:meta])*
+		$vis:vis struct $name:ident -> $localname:literal in $xmlns:literal {
+			$( $field:ident ( $field_name:ident : $field_ty:ty ) $( = $wire_name:literal )? ),* $(,)?
+		}
+	) => {
+		$(#[$meta])*
+		$vis struct $name {
+			$( pub $field_name: payload_element!(@storage $field, $field_ty), )*
+		}
+
+		impl $crate::stanza::payload::TryFromElement for $name {
+			const LOCALNAME: &'static str = $localname;
+			const XMLNS: &'static str = $xmlns;
+
+			fn try_from_element(el: $crate::stanza::tree::ElementPtr) -> ::std::result::Result<Self, $crate::stanza::payload::PayloadError> {
+				let borrowed = el.borrow();
+				$crate::stanza::payload::check_name_and_namespace(&borrowed, $localname, $xmlns)?;
+
+				#[allow(unused_mut)]
+				let mut known_attrs: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+				$( payload_element!(@known_attr known_attrs, $field $(, $wire_name)?); )*
+				$crate::stanza::payload::check_known_attributes(&borrowed, &known_attrs)?;
+
+				#[allow(unused_mut)]
+				let mut known_children: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+				$( payload_element!(@known_child known_children, $field, $field_ty); )*
+				$crate::stanza::payload::check_known_children(&borrowed, &known_children)?;
+
+				::std::result::Result::Ok($name {
+					$( $field_name: payload_element!(@extract borrowed, $field, $field_ty $(, $wire_name)?)?, )*
+				})
+			}
+		}
+
+		impl $crate::stanza::payload::IntoElement for $name {
+			fn into_element(self) -> $crate::stanza::tree::ElementPtr {
+				let nsuri = ::std::option::Option::Some($crate::stanza::payload::xmlns_rc($xmlns));
+				let el = $crate::stanza::tree::ElementPtr::new(nsuri.clone(), ::std::convert::TryInto::try_into($localname).unwrap());
+				$( payload_element!(@fill el, nsuri, self.$field_name, $field $(, $wire_name)?); )*
+				el
+			}
+		}
+	};
+
+	(@storage attribute, $ty:ty) => { $ty };
+	(@storage optional_attribute, $ty:ty) => { ::std::option::Option<$ty> };
+	(@storage child, $ty:ty) => { $ty };
+	(@storage optional_child, $ty:ty) => { ::std::option::Option<$ty> };
+	(@storage children, $ty:ty) => { ::std::vec::Vec<$ty> };
+	(@storage text, $ty:ty) => { $ty };
+
+	(@known_attr $vec:ident, attribute, $wire:literal) => { $vec.push($wire); };
+	(@known_attr $vec:ident, optional_attribute, $wire:literal) => { $vec.push($wire); };
+	(@known_attr $vec:ident, $kind:ident) => {};
+
+	(@known_child $vec:ident, child, $ty:ty) => { $vec.push(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME); };
+	(@known_child $vec:ident, optional_child, $ty:ty) => { $vec.push(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME); };
+	(@known_child $vec:ident, children, $ty:ty) => { $vec.push(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME); };
+	(@known_child $vec:ident, $kind:ident, $ty:ty) => {};
+
+	(@extract $borrowed:ident, attribute, $ty:ty, $wire:literal) => {
+		match $borrowed.attr.get($wire) {
+			::std::option::Option::Some(v) => ::std::result::Result::Ok(v.clone()),
+			::std::option::Option::None => ::std::result::Result::Err($crate::stanza::payload::PayloadError::MissingAttribute($wire)),
+		}
+	};
+	(@extract $borrowed:ident, optional_attribute, $ty:ty, $wire:literal) => {
+		::std::result::Result::Ok::<_, $crate::stanza::payload::PayloadError>($borrowed.attr.get($wire).cloned())
+	};
+	(@extract $borrowed:ident, text, $ty:ty) => {
+		::std::result::Result::Ok::<_, $crate::stanza::payload::PayloadError>($borrowed.get_text().unwrap_or_default())
+	};
+	(@extract $borrowed:ident, child, $ty:ty) => {
+		{
+			let selector = $crate::stanza::xmpp::ElementSelector::select_inside_parent(
+				::std::cell::Ref::clone(&$borrowed),
+				::std::option::Option::Some(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME.to_string()),
+				::std::option::Option::None,
+			);
+			match selector.find_first_child($borrowed.iter_children()) {
+				::std::option::Option::Some(child_ptr) => <$ty as $crate::stanza::payload::TryFromElement>::try_from_element(child_ptr),
+				::std::option::Option::None => ::std::result::Result::Err($crate::stanza::payload::PayloadError::MissingChild(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME)),
+			}
+		}
+	};
+	(@extract $borrowed:ident, optional_child, $ty:ty) => {
+		{
+			let selector = $crate::stanza::xmpp::ElementSelector::select_inside_parent(
+				::std::cell::Ref::clone(&$borrowed),
+				::std::option::Option::Some(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME.to_string()),
+				::std::option::Option::None,
+			);
+			match selector.find_first_child($borrowed.iter_children()) {
+				::std::option::Option::Some(child_ptr) => <$ty as $crate::stanza::payload::TryFromElement>::try_from_element(child_ptr).map(::std::option::Option::Some),
+				::std::option::Option::None => ::std::result::Result::Ok(::std::option::Option::None),
+			}
+		}
+	};
+	(@extract $borrowed:ident, children, $ty:ty) => {
+		{
+			let selector = $crate::stanza::xmpp::ElementSelector::select_inside_parent(
+				::std::cell::Ref::clone(&$borrowed),
+				::std::option::Option::Some(<$ty as $crate::stanza::payload::TryFromElement>::LOCALNAME.to_string()),
+				::std::option::Option::None,
+			);
+			selector.find_all($borrowed.iter_children())
+				.into_iter()
+				.map(<$ty as $crate::stanza::payload::TryFromElement>::try_from_element)
+				.collect::<::std::result::Result<::std::vec::Vec<$ty>, $crate::stanza::payload::PayloadError>>()
+		}
+	};
+
+	(@fill $el:ident, $nsuri:ident, $value:expr, attribute, $wire:literal) => {
+		$el.borrow_mut().attr.insert(::std::convert::TryInto::try_into($wire).unwrap(), $value);
+	};
+	(@fill $el:ident, $nsuri:ident, $value:expr, optional_attribute, $wire:literal) => {
+		if let ::std::option::Option::Some(v) = $value {
+			$el.borrow_mut().attr.insert(::std::convert::TryInto::try_into($wire).unwrap(), v);
+		}
+	};
+	(@fill $el:ident, $nsuri:ident, $value:expr, text) => {
+		$el.borrow_mut().text($value);
+	};
+	(@fill $el:ident, $nsuri:ident, $value:expr, child) => {
+		$el.borrow_mut().push($crate::stanza::tree::Node::Element($crate::stanza::payload::IntoElement::into_element($value))).unwrap();
+	};
+	(@fill $el:ident, $nsuri:ident, $value:expr, optional_child) => {
+		if let ::std::option::Option::Some(v) = $value {
+			$el.borrow_mut().push($crate::stanza::tree::Node::Element($crate::stanza::payload::IntoElement::into_element(v))).unwrap();
+		}
+	};
+	(@fill $el:ident, $nsuri:ident, $value:expr, children) => {
+		for v in $value {
+			$el.borrow_mut().push($crate::stanza::tree::Node::Element($crate::stanza::payload::IntoElement::into_element(v))).unwrap();
+		}
+	};
+}
+
+pub(crate) use payload_element;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::convert::TryInto;
+
+	payload_element!(
+		/// [XEP-0224](https://xmpp.org/extensions/xep-0224.html) Attention hint. Carries no fields.
+		#[derive(Clone, Debug, PartialEq)]
+		pub struct Attention -> "attention" in "urn:xmpp:attention:0" {}
+	);
+
+	payload_element!(
+		#[derive(Clone, Debug, PartialEq)]
+		pub struct Value -> "value" in "urn:example:payload" {
+			text(data: String),
+		}
+	);
+
+	payload_element!(
+		#[derive(Clone, Debug, PartialEq)]
+		pub struct Item -> "item" in "urn:example:payload" {
+			attribute(id: String) = "id",
+			optional_attribute(note: String) = "note",
+			text(label: String),
+		}
+	);
+
+	payload_element!(
+		#[derive(Clone, Debug, PartialEq)]
+		pub struct Basket -> "basket" in "urn:example:payload" {
+			attribute(owner: String) = "owner",
+			child(headline: Value),
+			children(items: Item),
+		}
+	);
+
+	#[test]
+	fn attention_round_trips() {
+		let el = tree::ElementPtr::new(
+			Some(super::xmlns_rc("urn:xmpp:attention:0")),
+			"attention".try_into().unwrap(),
+		);
+		let parsed = Attention::try_from_element(el).unwrap();
+		let rebuilt = parsed.into_element();
+		assert_eq!(rebuilt.borrow().localname, "attention");
+		assert_eq!(rebuilt.borrow().nsuri.as_ref().unwrap().as_str(), "urn:xmpp:attention:0");
+	}
+
+	#[test]
+	fn try_from_element_rejects_wrong_name() {
+		let el = tree::ElementPtr::new(
+			Some(super::xmlns_rc("urn:xmpp:attention:0")),
+			"not-attention".try_into().unwrap(),
+		);
+		assert!(matches!(Attention::try_from_element(el), Err(PayloadError::WrongName{..})));
+	}
+
+	#[test]
+	fn try_from_element_rejects_unknown_attribute() {
+		let mut attr = std::collections::HashMap::new();
+		attr.insert("bogus".try_into().unwrap(), "1".to_string());
+		let el = tree::ElementPtr::new_with_attr(
+			Some(super::xmlns_rc("urn:xmpp:attention:0")),
+			"attention".try_into().unwrap(),
+			Some(attr),
+		);
+		assert!(matches!(Attention::try_from_element(el), Err(PayloadError::UnknownAttribute(_))));
+	}
+
+	fn mk_item(id: &str, note: Option<&str>, label: &str) -> tree::ElementPtr {
+		let mut attr = std::collections::HashMap::new();
+		attr.insert("id".try_into().unwrap(), id.to_string());
+		if let Some(note) = note {
+			attr.insert("note".try_into().unwrap(), note.to_string());
+		}
+		// mirrors how a parser resolves an omitted wire-level `xmlns` to
+		// its parent's namespace before the element ever reaches the tree
+		let el = tree::ElementPtr::new_with_attr(Some(super::xmlns_rc("urn:example:payload")), "item".try_into().unwrap(), Some(attr));
+		el.borrow_mut().text(label.to_string());
+		el
+	}
+
+	#[test]
+	fn basket_round_trips_through_all_field_kinds_but_optional_child() {
+		let basket_el = tree::ElementPtr::new_with_attr(
+			Some(super::xmlns_rc("urn:example:payload")),
+			"basket".try_into().unwrap(),
+			Some({
+				let mut attr = std::collections::HashMap::new();
+				attr.insert("owner".try_into().unwrap(), "alice".to_string());
+				attr
+			}),
+		);
+		{
+			let headline_el = tree::ElementPtr::new(Some(super::xmlns_rc("urn:example:payload")), "value".try_into().unwrap());
+			headline_el.borrow_mut().text("fruit basket".to_string());
+			basket_el.borrow_mut().push(tree::Node::Element(headline_el)).unwrap();
+			basket_el.borrow_mut().push(tree::Node::Element(mk_item("1", None, "apple"))).unwrap();
+			basket_el.borrow_mut().push(tree::Node::Element(mk_item("2", Some("ripe"), "banana"))).unwrap();
+		}
+
+		let basket = Basket::try_from_element(basket_el).unwrap();
+		assert_eq!(basket.owner, "alice");
+		assert_eq!(basket.headline.data, "fruit basket");
+		assert_eq!(basket.items.len(), 2);
+		assert_eq!(basket.items[0].id, "1");
+		assert!(basket.items[0].note.is_none());
+		assert_eq!(basket.items[0].label, "apple");
+		assert_eq!(basket.items[1].id, "2");
+		assert_eq!(basket.items[1].note.as_deref(), Some("ripe"));
+
+		let rebuilt = basket.clone().into_element();
+		let reparsed = Basket::try_from_element(rebuilt).unwrap();
+		assert_eq!(reparsed, basket);
+	}
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::fmt::Write;
+
+/// Whether the breaks inside a [`Token::Begin`] group all fire together
+/// once the group doesn't fit flat ([`Consistent`](Self::Consistent)),
+/// or only fire as needed, break by break, to keep the *next* chunk
+/// within the remaining width ([`Inconsistent`](Self::Inconsistent)).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BreakStyle {
+	Consistent,
+	Inconsistent,
+}
+
+/// A token in the stream consumed by [`print`], following Oppen's
+/// pretty-printing algorithm (the same one rustc's classic `pp.rs` used):
+/// [`Begin`](Self::Begin)/[`End`](Self::End) bracket a group whose
+/// flat-layout width is measured by a scanning pass before anything is
+/// printed; [`Break`](Self::Break) is a point inside a group that may
+/// turn into a newline; [`Str`](Self::Str) is literal, never-broken text.
+///
+/// Unlike the classic algorithm, a [`Break`] carries its own `depth`
+/// rather than inheriting one from its enclosing [`Begin`]: the caller
+/// already knows each token's tree depth when it builds the stream (it's
+/// just the element nesting depth), so there's no need for `print` to
+/// track an indent stack alongside the fits stack -- it only has to
+/// decide *whether* a break fires, not how far to indent it.
+#[derive(Clone, Debug)]
+pub enum Token {
+	Begin(BreakStyle),
+	End,
+	Str(String),
+	/// A potential line break. `blanks` spaces are printed in its place
+	/// when the break doesn't fire; when it does fire, a newline plus
+	/// `depth` repetitions of `print`'s `indent_unit` are printed instead.
+	Break{blanks: usize, depth: usize},
+}
+
+/// Scanning pass: compute the flat-layout width of every [`Token::Begin`]
+/// group (distance to its matching [`Token::End`]) and every
+/// [`Token::Break`] (distance to the next break, or the group's `End`,
+/// whichever comes first).
+///
+/// `pending` tracks tokens whose size is not yet known, in nesting order
+/// -- a stand-in for the ring buffer the streaming version of this
+/// algorithm uses to bound memory; since we hold the whole token stream
+/// in memory already, a plain stack does the same job.
+fn compute_sizes(tokens: &[Token]) -> Vec<isize> {
+	let mut sizes = vec![0isize; tokens.len()];
+	let mut pending: Vec<usize> = Vec::new();
+	let mut right_total: isize = 0;
+
+	for (i, tok) in tokens.iter().enumerate() {
+		match tok {
+			Token::Begin(..) => {
+				sizes[i] = -right_total;
+				pending.push(i);
+			},
+			Token::End => {
+				loop {
+					let j = pending.pop().expect("Token::End with no matching Token::Begin");
+					sizes[j] += right_total;
+					if matches!(tokens[j], Token::Begin(..)) {
+						break;
+					}
+				}
+			},
+			Token::Break{blanks, ..} => {
+				if let Some(&j) = pending.last() {
+					if matches!(tokens[j], Token::Break{..}) {
+						sizes[j] += right_total;
+						pending.pop();
+					}
+				}
+				sizes[i] = -right_total;
+				pending.push(i);
+				right_total += *blanks as isize;
+			},
+			Token::Str(s) => {
+				right_total += s.len() as isize;
+			},
+		}
+	}
+
+	// A trailing Break after the outermost End (the usual way a caller
+	// marks "about to print the closing delimiter of the whole
+	// document") never gets closed by a following Break or End, so it's
+	// still pending here even in a well-formed stream; resolve it the
+	// same way End would, against the stream's total width. Anything
+	// else left open only happens for a genuinely unbalanced stream, and
+	// gets the same treatment rather than panicking.
+	while let Some(j) = pending.pop() {
+		sizes[j] += right_total;
+	}
+
+	sizes
+}
+
+/// Printing pass: lay `tokens` out against `max_width` columns, using the
+/// sizes [`compute_sizes`] already assigned. A group that fits in the
+/// remaining width at the point it opens is printed flat; one that
+/// doesn't prints every [`Token::Break`] directly inside it according to
+/// its [`BreakStyle`] (consistent groups always break, inconsistent ones
+/// break only when the next chunk wouldn't otherwise fit).
+pub fn print<W: Write>(tokens: &[Token], max_width: usize, indent_unit: &str, out: &mut W) -> fmt::Result {
+	let sizes = compute_sizes(tokens);
+	// One entry per currently-open group: whether it is being printed flat,
+	// and the group's own break style (used by not-flat groups to decide
+	// whether a given break inside them fires).
+	let mut fits_stack: Vec<(bool, BreakStyle)> = Vec::new();
+	let mut column: usize = 0;
+
+	for (i, tok) in tokens.iter().enumerate() {
+		match tok {
+			Token::Begin(style) => {
+				let remaining = max_width.saturating_sub(column);
+				let flat = sizes[i] >= 0 && (sizes[i] as usize) <= remaining;
+				fits_stack.push((flat, *style));
+			},
+			Token::End => {
+				fits_stack.pop();
+			},
+			Token::Break{blanks, depth} => {
+				// A break with no enclosing group (e.g. the one marking
+				// a document's closing delimiter, right after its
+				// outermost End) has no group fits-decision to inherit,
+				// so fall back to measuring it against the remaining
+				// width directly, same as a Begin would.
+				let (flat, style) = match fits_stack.last() {
+					Some(&entry) => entry,
+					None => {
+						let remaining = max_width.saturating_sub(column);
+						(sizes[i] >= 0 && (sizes[i] as usize) <= remaining, BreakStyle::Consistent)
+					},
+				};
+				let fires = !flat && match style {
+					BreakStyle::Consistent => true,
+					BreakStyle::Inconsistent => {
+						let remaining = max_width.saturating_sub(column);
+						sizes[i] < 0 || (sizes[i] as usize) > remaining
+					},
+				};
+				if fires {
+					out.write_char('\n')?;
+					for _ in 0..*depth {
+						out.write_str(indent_unit)?;
+					}
+					column = depth * indent_unit.len();
+				} else {
+					for _ in 0..*blanks {
+						out.write_char(' ')?;
+					}
+					column += blanks;
+				}
+			},
+			Token::Str(s) => {
+				out.write_str(s)?;
+				column += s.len();
+			},
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn render(tokens: Vec<Token>, max_width: usize) -> String {
+		let mut out = String::new();
+		print(&tokens, max_width, "  ", &mut out).unwrap();
+		out
+	}
+
+	#[test]
+	fn short_group_stays_flat() {
+		let tokens = vec![
+			Token::Begin(BreakStyle::Consistent),
+			Token::Str("<a>".to_string()),
+			Token::Break{blanks: 0, depth: 1},
+			Token::Str("<b/>".to_string()),
+			Token::Break{blanks: 0, depth: 1},
+			Token::Str("<c/>".to_string()),
+			Token::End,
+			Token::Break{blanks: 0, depth: 0},
+			Token::Str("</a>".to_string()),
+		];
+		assert_eq!(render(tokens, 80), "<a><b/><c/></a>");
+	}
+
+	#[test]
+	fn over_long_consistent_group_breaks_every_break() {
+		let tokens = vec![
+			Token::Begin(BreakStyle::Consistent),
+			Token::Str("<a>".to_string()),
+			Token::Break{blanks: 0, depth: 1},
+			Token::Str("<b/>".to_string()),
+			Token::Break{blanks: 0, depth: 1},
+			Token::Str("<c/>".to_string()),
+			Token::End,
+			Token::Break{blanks: 0, depth: 0},
+			Token::Str("</a>".to_string()),
+		];
+		assert_eq!(render(tokens, 5), "<a>\n  <b/>\n  <c/>\n</a>");
+	}
+}
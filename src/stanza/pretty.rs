@@ -0,0 +1,169 @@
+use std::cell::Ref;
+use std::fmt;
+use std::fmt::Write;
+
+use super::tree;
+
+const RESET: &str = "\x1b[0m";
+const COL_TAG: &str = "\x1b[36m";
+const COL_NS: &str = "\x1b[34m";
+const COL_ATTR_NAME: &str = "\x1b[33m";
+const COL_ATTR_VALUE: &str = "\x1b[32m";
+const COL_TEXT: &str = "\x1b[37m";
+
+/// Options for [`pretty_print`].
+pub struct PrettyPrintOptions {
+	/// Unit of indentation prepended once per nesting level.
+	pub indent: String,
+	/// Whether to wrap localnames, namespace URIs, attribute names and
+	/// values, and text nodes in ANSI color escapes.
+	pub color: bool,
+	/// Text nodes longer than this are truncated (on a char boundary)
+	/// and suffixed with `…`. `None` means no truncation.
+	pub max_text_len: Option<usize>,
+}
+
+impl Default for PrettyPrintOptions {
+	fn default() -> PrettyPrintOptions {
+		PrettyPrintOptions{
+			indent: "  ".to_string(),
+			color: false,
+			max_text_len: None,
+		}
+	}
+}
+
+fn colored<W, D>(out: &mut W, color: &str, enabled: bool, s: D) -> fmt::Result
+	where W: Write, D: fmt::Display
+{
+	if enabled {
+		write!(out, "{}{}{}", color, s, RESET)
+	} else {
+		write!(out, "{}", s)
+	}
+}
+
+fn write_text<'a, W>(out: &'a mut W, opts: &'a PrettyPrintOptions, s: &'a str) -> fmt::Result
+	where W: Write
+{
+	let truncated = match opts.max_text_len {
+		Some(max_len) if s.len() > max_len => {
+			let mut end = max_len;
+			while !s.is_char_boundary(end) {
+				end -= 1;
+			}
+			format!("{}…", &s[..end])
+		},
+		_ => s.to_string(),
+	};
+	colored(out, COL_TEXT, opts.color, truncated)
+}
+
+fn write_node<'a, W>(out: &'a mut W, opts: &'a PrettyPrintOptions, node: &'a tree::Node, depth: usize) -> fmt::Result
+	where W: Write
+{
+	out.write_str(&opts.indent.repeat(depth))?;
+	match node {
+		tree::Node::Text(s) => {
+			write_text(out, opts, s)?;
+			out.write_str("\n")
+		},
+		tree::Node::Element(eptr) => write_element(out, opts, eptr.borrow(), depth),
+	}
+}
+
+fn write_element<'a, W>(out: &'a mut W, opts: &'a PrettyPrintOptions, el: Ref<'a, tree::Element>, depth: usize) -> fmt::Result
+	where W: Write
+{
+	out.write_str("<")?;
+	colored(out, COL_TAG, opts.color, &el.localname)?;
+	if let Some(nsuri) = el.nsuri.as_ref() {
+		out.write_str(" xmlns=\"")?;
+		colored(out, COL_NS, opts.color, nsuri.as_str())?;
+		out.write_str("\"")?;
+	}
+	for (k, v) in el.attr.iter() {
+		let (attr_nsuri, attr_localname) = k.decompose();
+		out.write_str(" ")?;
+		if let Some(attr_nsuri) = attr_nsuri {
+			colored(out, COL_NS, opts.color, AsRef::<str>::as_ref(attr_nsuri))?;
+			out.write_str(":")?;
+		}
+		colored(out, COL_ATTR_NAME, opts.color, attr_localname)?;
+		out.write_str("=\"")?;
+		colored(out, COL_ATTR_VALUE, opts.color, v)?;
+		out.write_str("\"")?;
+	}
+	if el.len() == 0 {
+		return out.write_str("/>\n");
+	}
+	out.write_str(">\n")?;
+	for child in el.iter() {
+		write_node(out, opts, child, depth + 1)?;
+	}
+	out.write_str(&opts.indent.repeat(depth))?;
+	out.write_str("</")?;
+	colored(out, COL_TAG, opts.color, &el.localname)?;
+	out.write_str(">\n")
+}
+
+/// Render `el` and its descendants as indented, one-element-per-line
+/// text, optionally with ANSI coloring, mirroring Prosody's
+/// `util.stanza:pretty_print()`. Useful for diagnostics; the output is
+/// not meant to be reparsed as XML (attribute namespace prefixes are
+/// synthesized rather than declared, for instance).
+pub fn pretty_print<'a>(el: Ref<'a, tree::Element>, opts: &PrettyPrintOptions) -> String {
+	let mut out = String::new();
+	// write_element only fails if the underlying `Write` does, which
+	// never happens for `String`.
+	write_element(&mut out, opts, el, 0).unwrap();
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::convert::TryInto;
+
+	#[test]
+	fn pretty_print_self_closing_element() {
+		let el = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		let s = pretty_print(el.borrow(), &PrettyPrintOptions::default());
+		assert_eq!(s, "<iq/>\n");
+	}
+
+	#[test]
+	fn pretty_print_nests_children_one_per_line() {
+		let el = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		el.borrow_mut().tag(None, "body".try_into().unwrap(), None).borrow_mut().text("hi".try_into().unwrap());
+		let s = pretty_print(el.borrow(), &PrettyPrintOptions::default());
+		assert_eq!(s, "<message>\n  <body>\n    hi\n  </body>\n</message>\n");
+	}
+
+	#[test]
+	fn pretty_print_includes_namespace_and_attributes() {
+		let ns = std::rc::Rc::new(rxml::CData::from_string("jabber:client".to_string()).unwrap());
+		let mut attr = std::collections::HashMap::new();
+		attr.insert("type".try_into().unwrap(), "chat".to_string());
+		let el = tree::ElementPtr::new_with_attr(Some(ns), "message".try_into().unwrap(), Some(attr));
+		let s = pretty_print(el.borrow(), &PrettyPrintOptions::default());
+		assert_eq!(s, "<message xmlns=\"jabber:client\" type=\"chat\"/>\n");
+	}
+
+	#[test]
+	fn pretty_print_truncates_long_text() {
+		let el = tree::ElementPtr::new(None, "body".try_into().unwrap());
+		el.borrow_mut().text("Hello World!".try_into().unwrap());
+		let opts = PrettyPrintOptions{ max_text_len: Some(5), ..PrettyPrintOptions::default() };
+		let s = pretty_print(el.borrow(), &opts);
+		assert_eq!(s, "<body>\n  Hello…\n</body>\n");
+	}
+
+	#[test]
+	fn pretty_print_colored_wraps_tag_in_ansi_escapes() {
+		let el = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		let opts = PrettyPrintOptions{ color: true, ..PrettyPrintOptions::default() };
+		let s = pretty_print(el.borrow(), &opts);
+		assert_eq!(s, format!("<{}iq{}/>\n", COL_TAG, RESET));
+	}
+}
@@ -0,0 +1,264 @@
+use std::cell::Ref;
+use std::rc::Rc;
+
+use rxml::CData;
+
+use super::tree;
+use super::xmpp::ElementSelector;
+
+/// Outcome of a [`Handler`] being offered a stanza.
+pub enum HandlerResult {
+	/// The stanza was dealt with; the router stops looking for further
+	/// matches.
+	Handled,
+	/// The handler declined the stanza; the router keeps trying the
+	/// remaining `(StanzaMatcher, Handler)` pairs.
+	Passed,
+	/// The handler attempted to deal with the stanza but failed.
+	Error(String),
+}
+
+/// A callback invoked by [`Router::route`] for stanzas accepted by the
+/// associated [`StanzaMatcher`].
+pub type Handler = Box<dyn Fn(tree::ElementPtr) -> HandlerResult>;
+
+/// Predicate over an [`tree::Element`], matching on its `localname`, a
+/// required namespace, its `type` attribute, and the presence of a
+/// required child element.
+///
+/// Mirrors the kind of dispatch logic `core_stanza_router` performs against
+/// `message`/`presence`/`iq` stanzas, but expressed as a reusable, composable
+/// predicate over the [`tree::Element`] type.
+pub struct StanzaMatcher {
+	localname: Option<String>,
+	nsuri: Option<Rc<CData>>,
+	types: Option<Vec<String>>,
+	required_child: Option<(Option<Rc<CData>>, String)>,
+}
+
+impl StanzaMatcher {
+	/// A matcher that accepts any stanza. Narrow it down with the builder
+	/// methods below.
+	pub fn new() -> StanzaMatcher {
+		StanzaMatcher{
+			localname: None,
+			nsuri: None,
+			types: None,
+			required_child: None,
+		}
+	}
+
+	/// Only match stanzas with the given `localname` (e.g. `"iq"`).
+	pub fn with_localname(mut self, localname: &str) -> StanzaMatcher {
+		self.localname = Some(localname.to_string());
+		self
+	}
+
+	/// Only match stanzas in the given namespace.
+	pub fn with_namespace(mut self, nsuri: Rc<CData>) -> StanzaMatcher {
+		self.nsuri = Some(nsuri);
+		self
+	}
+
+	/// Only match stanzas whose `type` attribute is one of `types`.
+	pub fn with_types(mut self, types: Vec<String>) -> StanzaMatcher {
+		self.types = Some(types);
+		self
+	}
+
+	/// Only match stanzas which have a child element identified by
+	/// `{uri}name` (`uri` may be omitted, in which case a child in the
+	/// stanza's own namespace or with no namespace of its own matches).
+	pub fn with_required_child(mut self, nsuri: Option<Rc<CData>>, localname: &str) -> StanzaMatcher {
+		self.required_child = Some((nsuri, localname.to_string()));
+		self
+	}
+
+	/// Test whether `el` satisfies all of the configured predicates.
+	pub fn matches<'a>(&self, el: Ref<'a, tree::Element>) -> bool {
+		if let Some(localname) = self.localname.as_ref() {
+			if el.localname != localname.as_str() {
+				return false;
+			}
+		}
+
+		if let Some(nsuri) = self.nsuri.as_ref() {
+			match el.nsuri.as_ref() {
+				Some(el_nsuri) => if el_nsuri != nsuri {
+					return false;
+				},
+				None => return false,
+			}
+		}
+
+		if let Some(types) = self.types.as_ref() {
+			match el.attr.get("type") {
+				Some(type_) => if !types.iter().any(|t| t == type_) {
+					return false;
+				},
+				None => return false,
+			}
+		}
+
+		if let Some((child_nsuri, child_localname)) = self.required_child.as_ref() {
+			let selector = ElementSelector::select_inside_parent(
+				Ref::clone(&el),
+				Some(child_localname.clone()),
+				child_nsuri.clone(),
+			);
+			if selector.find_first_child(el.iter_children()).is_none() {
+				return false;
+			}
+		}
+
+		true
+	}
+}
+
+/// An ordered list of `(StanzaMatcher, Handler)` pairs, dispatching an
+/// incoming stanza to the first handler whose matcher accepts it.
+pub struct Router {
+	routes: Vec<(StanzaMatcher, Handler)>,
+}
+
+impl Router {
+	pub fn new() -> Router {
+		Router{routes: Vec::new()}
+	}
+
+	/// Append a `(matcher, handler)` pair to the end of the route list.
+	pub fn add(&mut self, matcher: StanzaMatcher, handler: Handler) {
+		self.routes.push((matcher, handler));
+	}
+
+	/// Dispatch `el` to the first matching handler. Handlers that return
+	/// [`HandlerResult::Passed`] are skipped over in favor of the next
+	/// matching route. Returns `HandlerResult::Passed` if no route
+	/// matches, or no matching handler accepted the stanza.
+	pub fn route(&self, el: tree::ElementPtr) -> HandlerResult {
+		for (matcher, handler) in self.routes.iter() {
+			if !matcher.matches(el.borrow()) {
+				continue;
+			}
+			match handler(el.clone()) {
+				HandlerResult::Passed => continue,
+				other => return other,
+			}
+		}
+		HandlerResult::Passed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::convert::TryInto;
+
+	fn mknsuri(s: &'static str) -> Rc<CData> {
+		Rc::new(CData::from_string(s.to_string()).unwrap())
+	}
+
+	#[test]
+	fn stanza_matcher_matches_by_localname() {
+		let matcher = StanzaMatcher::new().with_localname("iq");
+		let iq = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		let message = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		assert!(matcher.matches(iq.borrow()));
+		assert!(!matcher.matches(message.borrow()));
+	}
+
+	#[test]
+	fn stanza_matcher_matches_by_namespace() {
+		let matcher = StanzaMatcher::new().with_namespace(mknsuri("jabber:client"));
+		let in_ns = tree::ElementPtr::new(Some(mknsuri("jabber:client")), "iq".try_into().unwrap());
+		let out_of_ns = tree::ElementPtr::new(Some(mknsuri("jabber:server")), "iq".try_into().unwrap());
+		let no_ns = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		assert!(matcher.matches(in_ns.borrow()));
+		assert!(!matcher.matches(out_of_ns.borrow()));
+		assert!(!matcher.matches(no_ns.borrow()));
+	}
+
+	#[test]
+	fn stanza_matcher_matches_by_type() {
+		let matcher = StanzaMatcher::new().with_types(vec!["get".to_string(), "set".to_string()]);
+		let get = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		get.borrow_mut().attr.insert("type".try_into().unwrap(), "get".to_string());
+		let result = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		result.borrow_mut().attr.insert("type".try_into().unwrap(), "result".to_string());
+		let untyped = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		assert!(matcher.matches(get.borrow()));
+		assert!(!matcher.matches(result.borrow()));
+		assert!(!matcher.matches(untyped.borrow()));
+	}
+
+	#[test]
+	fn stanza_matcher_matches_by_required_child() {
+		let matcher = StanzaMatcher::new().with_required_child(Some(mknsuri("jabber:iq:roster")), "query");
+		let with_child = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		with_child.borrow_mut().tag(Some(mknsuri("jabber:iq:roster")), "query".try_into().unwrap(), None);
+		let without_child = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		assert!(matcher.matches(with_child.borrow()));
+		assert!(!matcher.matches(without_child.borrow()));
+	}
+
+	#[test]
+	fn router_dispatches_to_first_matching_handler() {
+		let mut router = Router::new();
+		let calls: Rc<RefCell<Vec<&'static str>>> = Rc::new(RefCell::new(Vec::new()));
+
+		let iq_calls = calls.clone();
+		router.add(StanzaMatcher::new().with_localname("iq"), Box::new(move |_el| {
+			iq_calls.borrow_mut().push("iq");
+			HandlerResult::Handled
+		}));
+
+		let message_calls = calls.clone();
+		router.add(StanzaMatcher::new().with_localname("message"), Box::new(move |_el| {
+			message_calls.borrow_mut().push("message");
+			HandlerResult::Handled
+		}));
+
+		let iq = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		match router.route(iq) {
+			HandlerResult::Handled => (),
+			_ => panic!("expected Handled"),
+		}
+		assert_eq!(*calls.borrow(), vec!["iq"]);
+	}
+
+	#[test]
+	fn router_falls_through_on_passed() {
+		let mut router = Router::new();
+		router.add(StanzaMatcher::new().with_localname("iq"), Box::new(|_el| HandlerResult::Passed));
+		router.add(StanzaMatcher::new().with_localname("iq"), Box::new(|_el| HandlerResult::Handled));
+
+		let iq = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		match router.route(iq) {
+			HandlerResult::Handled => (),
+			_ => panic!("expected Handled"),
+		}
+	}
+
+	#[test]
+	fn router_returns_passed_when_nothing_matches() {
+		let router = Router::new();
+		let iq = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		match router.route(iq) {
+			HandlerResult::Passed => (),
+			_ => panic!("expected Passed"),
+		}
+	}
+
+	#[test]
+	fn router_propagates_handler_errors() {
+		let mut router = Router::new();
+		router.add(StanzaMatcher::new().with_localname("iq"), Box::new(|_el| HandlerResult::Error("boom".to_string())));
+
+		let iq = tree::ElementPtr::new(None, "iq".try_into().unwrap());
+		match router.route(iq) {
+			HandlerResult::Error(msg) => assert_eq!(msg, "boom"),
+			_ => panic!("expected Error"),
+		}
+	}
+}
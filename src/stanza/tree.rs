@@ -60,7 +60,7 @@ pub enum MapElementsError<T> {
 	External(T),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct ElementPtr(Rc<RefCell<Element>>);
 
 #[derive(Clone)]
@@ -99,6 +99,30 @@ impl ElementPtr {
 	pub fn deep_clone(&self) -> ElementPtr {
 		ElementPtr::wrap(self.borrow().deep_clone())
 	}
+
+	/// Look up a descendant element, attribute, or text node by path,
+	/// mirroring Prosody's `util.stanza:find`. See
+	/// [`fake_xpath::find`](super::fake_xpath::find) for the path
+	/// syntax.
+	pub fn find(&self, path: &str) -> Option<Node> {
+		super::fake_xpath::find(self.borrow(), path)
+	}
+
+	/// Render this element and its descendants as indented, one-element-
+	/// per-line text, mirroring Prosody's `util.stanza:pretty_print()`.
+	/// See [`pretty::pretty_print`](super::pretty::pretty_print).
+	pub fn pretty_print(&self, opts: &super::pretty::PrettyPrintOptions) -> String {
+		super::pretty::pretty_print(self.borrow(), opts)
+	}
+
+	/// Like [`pretty_print`](ElementPtr::pretty_print), but with ANSI
+	/// coloring enabled.
+	pub fn pretty_print_colored(&self) -> String {
+		self.pretty_print(&super::pretty::PrettyPrintOptions{
+			color: true,
+			..Default::default()
+		})
+	}
 }
 
 impl PartialEq for ElementPtr {
@@ -405,6 +429,43 @@ impl Element {
 		self.children.iter_children()
 	}
 
+	/// Alias of [`iter_children`](Element::iter_children), matching
+	/// Prosody's `childtags()` naming.
+	pub fn childtags<'a>(&'a self) -> ChildElementIterator {
+		self.iter_children()
+	}
+
+	/// Child element pointers whose `localname` is `localname` and whose
+	/// namespace is `namespace`. A `namespace` of `None` inherits this
+	/// element's own namespace, matching Prosody's `get_child` semantics.
+	pub fn get_children_by_name<'a>(&'a self, localname: &str, namespace: Option<&str>) -> impl Iterator<Item = ElementPtr> + 'a {
+		let localname = localname.to_string();
+		let namespace = namespace.map(|ns| ns.to_string());
+		let parent_nsuri = self.nsuri.clone();
+		self.iter_children().filter(move |child_ptr| {
+			let child = child_ptr.borrow();
+			if child.localname.as_str() != localname {
+				return false;
+			}
+			match namespace.as_ref() {
+				Some(namespace) => match child.nsuri.as_ref() {
+					Some(child_nsuri) => child_nsuri.as_str() == namespace,
+					None => false,
+				},
+				None => match (parent_nsuri.as_ref(), child.nsuri.as_ref()) {
+					(Some(parent_nsuri), Some(child_nsuri)) => parent_nsuri.as_str() == child_nsuri.as_str(),
+					(None, None) => true,
+					_ => false,
+				},
+			}
+		})
+	}
+
+	/// The first child matching [`get_children_by_name`](Element::get_children_by_name), if any.
+	pub fn get_child(&self, localname: &str, namespace: Option<&str>) -> Option<ElementPtr> {
+		self.get_children_by_name(localname, namespace).next()
+	}
+
 	pub fn element_view(&self) -> ElementView {
 		self.children.element_view()
 	}
@@ -616,13 +677,18 @@ mod tests {
 	}
 
 	#[test]
-	fn element_tag_inherits_nsuri_from_parent() {
+	fn element_tag_does_not_implicitly_resolve_nsuri_from_parent() {
+		// `tag`'s `xmlns: None` is stored on the child literally, not
+		// eagerly resolved against the parent -- it's the same "omitted
+		// means parent's namespace" convention `ElementSelector::select_inside_parent`
+		// and the `xml` formatter apply lazily wherever a nsuri is
+		// actually needed, not something `tag` bakes in up front.
 		let nsuri = Some(Rc::new("uri:foobar".try_into().unwrap()));
 		let el_ptr = ElementPtr::new(nsuri.clone(), "message".try_into().unwrap());
 		{
 			let body_ptr = el_ptr.borrow_mut().tag(None, "body".try_into().unwrap(), None);
 			let body = body_ptr.borrow();
-			assert_eq!(body.nsuri, nsuri);
+			assert_eq!(body.nsuri, None);
 			assert_eq!(body.localname, "body");
 			assert!(body.attr.is_empty());
 			assert!(body.children.is_empty());
@@ -807,6 +873,75 @@ mod tests {
 		assert_eq!(el.borrow().len(), 1);
 	}
 
+	#[test]
+	fn element_ptr_find_delegates_to_fake_xpath() {
+		let root = prep_message();
+		let result = root.find("body").unwrap();
+		let result = result.as_element_ptr().unwrap();
+		assert!(ElementPtr::ptr_eq(&result, &root.borrow()[0].as_element_ptr().unwrap()));
+	}
+
+	#[test]
+	fn element_childtags_skips_text_nodes() {
+		let msg = prep_message();
+		msg.borrow_mut().text("trailing text".try_into().unwrap());
+		assert_eq!(msg.borrow().childtags().count(), 2);
+	}
+
+	#[test]
+	fn element_get_children_by_name_filters_by_localname() {
+		let msg = prep_message();
+		let bodies: Vec<_> = msg.borrow().get_children_by_name("body", None).collect();
+		assert_eq!(bodies.len(), 1);
+		assert_eq!(bodies[0].borrow().localname, rxml::Name::try_from("body").unwrap());
+	}
+
+	#[test]
+	fn element_get_children_by_name_defaults_namespace_to_parent() {
+		let ns = Some(std::rc::Rc::new(rxml::CData::from_string("jabber:client".to_string()).unwrap()));
+		let msg = ElementPtr::new(ns.clone(), "message".try_into().unwrap());
+		msg.borrow_mut().tag(ns.clone(), "body".try_into().unwrap(), None);
+		msg.borrow_mut().tag(None, "body".try_into().unwrap(), None);
+		let bodies: Vec<_> = msg.borrow().get_children_by_name("body", None).collect();
+		assert_eq!(bodies.len(), 1);
+		assert!(bodies[0].borrow().nsuri.is_some());
+	}
+
+	#[test]
+	fn element_get_children_by_name_explicit_namespace_overrides_parent_default() {
+		let ns = Some(std::rc::Rc::new(rxml::CData::from_string("jabber:client".to_string()).unwrap()));
+		let other_ns = Some(std::rc::Rc::new(rxml::CData::from_string("urn:other".to_string()).unwrap()));
+		let msg = ElementPtr::new(ns.clone(), "message".try_into().unwrap());
+		msg.borrow_mut().tag(other_ns, "body".try_into().unwrap(), None);
+		let bodies: Vec<_> = msg.borrow().get_children_by_name("body", Some("urn:other")).collect();
+		assert_eq!(bodies.len(), 1);
+	}
+
+	#[test]
+	fn element_get_child_returns_first_match() {
+		let msg = prep_message();
+		let body = msg.borrow().get_child("body", None).unwrap();
+		assert!(ElementPtr::ptr_eq(&body, &msg.borrow()[0].as_element_ptr().unwrap()));
+	}
+
+	#[test]
+	fn element_get_child_returns_none_when_absent() {
+		let msg = prep_message();
+		assert!(msg.borrow().get_child("nonexistent", None).is_none());
+	}
+
+	#[test]
+	fn element_ptr_pretty_print_delegates_to_pretty() {
+		let el = ElementPtr::new(None, "iq".try_into().unwrap());
+		assert_eq!(el.pretty_print(&crate::stanza::pretty::PrettyPrintOptions::default()), "<iq/>\n");
+	}
+
+	#[test]
+	fn element_ptr_pretty_print_colored_enables_color() {
+		let el = ElementPtr::new(None, "iq".try_into().unwrap());
+		assert!(el.pretty_print_colored().contains("\x1b["));
+	}
+
 	#[test]
 	fn element_unprotected_elements_can_be_inserted_into_separate_trees() {
 		let p1 = ElementPtr::new(None, "message".try_into().unwrap());
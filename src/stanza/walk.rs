@@ -0,0 +1,159 @@
+use std::cell::Ref;
+
+use super::tree;
+
+/// All descendant elements of `root`, in pre-order (a parent is yielded
+/// before its children).
+///
+/// `root` itself is not yielded. Only one level of `Ref` borrow is ever
+/// held at a time: each step borrows a node just long enough to clone its
+/// children's `Rc`s onto the stack, then releases the borrow before
+/// descending, so a [`Visitor`]/handler may freely mutate earlier-yielded
+/// elements.
+pub struct DescendantIter {
+	stack: Vec<tree::ElementPtr>,
+}
+
+impl Iterator for DescendantIter {
+	type Item = tree::ElementPtr;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let next = self.stack.pop()?;
+		let mut children: Vec<tree::ElementPtr> = next.borrow().iter_children().collect();
+		children.reverse();
+		self.stack.extend(children);
+		Some(next)
+	}
+}
+
+pub fn descendants(root: &tree::ElementPtr) -> DescendantIter {
+	let mut stack: Vec<tree::ElementPtr> = root.borrow().iter_children().collect();
+	stack.reverse();
+	DescendantIter{stack}
+}
+
+/// All descendant elements of `root`, in post-order (a parent is yielded
+/// only after all of its children).
+pub struct PostorderDescendantIter {
+	items: std::vec::IntoIter<tree::ElementPtr>,
+}
+
+impl Iterator for PostorderDescendantIter {
+	type Item = tree::ElementPtr;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.items.next()
+	}
+}
+
+fn collect_postorder(el: &tree::ElementPtr, out: &mut Vec<tree::ElementPtr>) {
+	let children: Vec<tree::ElementPtr> = el.borrow().iter_children().collect();
+	for child in children {
+		collect_postorder(&child, out);
+		out.push(child);
+	}
+}
+
+pub fn descendants_postorder(root: &tree::ElementPtr) -> PostorderDescendantIter {
+	let mut items = Vec::new();
+	collect_postorder(root, &mut items);
+	PostorderDescendantIter{items: items.into_iter()}
+}
+
+/// Callback interface for [`walk`].
+pub trait Visitor {
+	/// Called for every element, including `root`, before its children.
+	/// `depth` is 0 for `root`.
+	fn visit_element<'a>(&mut self, depth: usize, el: Ref<'a, tree::Element>);
+
+	/// Called for every text node, in document order relative to its
+	/// surrounding sibling elements. `depth` is the depth of the text
+	/// node's parent element plus one.
+	fn visit_text(&mut self, depth: usize, text: &str);
+}
+
+fn walk_at<V: Visitor>(el: &tree::ElementPtr, depth: usize, visitor: &mut V) {
+	// Collect this level's children (cloning the Rcs) before recursing,
+	// so the `Ref` borrow below is released before we touch any child --
+	// a child handler may otherwise want to mutate `el` itself.
+	let children: Vec<tree::Node> = {
+		let borrowed = el.borrow();
+		visitor.visit_element(depth, Ref::clone(&borrowed));
+		borrowed.iter().cloned().collect()
+	};
+
+	for child in children {
+		match child {
+			tree::Node::Text(s) => visitor.visit_text(depth + 1, &s),
+			tree::Node::Element(child_ptr) => walk_at(&child_ptr, depth + 1, visitor),
+		}
+	}
+}
+
+/// Depth-first, pre-order walk of `root` and its descendants, dispatching
+/// to `visitor` as each element/text node is encountered.
+pub fn walk<V: Visitor>(root: &tree::ElementPtr, visitor: &mut V) {
+	walk_at(root, 0, visitor);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::convert::TryInto;
+
+	fn prep_nested() -> tree::ElementPtr {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		{
+			let mut root_mut = root.borrow_mut();
+			let forwarded = root_mut.tag(None, "forwarded".try_into().unwrap(), None);
+			forwarded.borrow_mut().tag(None, "delay".try_into().unwrap(), None);
+			forwarded.borrow_mut().tag(None, "message".try_into().unwrap(), None).borrow_mut().text("hi".try_into().unwrap());
+			root_mut.tag(None, "body".try_into().unwrap(), None);
+		}
+		root
+	}
+
+	#[test]
+	fn descendants_visits_every_nested_element_in_preorder() {
+		let root = prep_nested();
+		let names: Vec<String> = descendants(&root).map(|el| el.borrow().localname.to_string()).collect();
+		assert_eq!(names, vec!["forwarded", "delay", "message", "body"]);
+	}
+
+	#[test]
+	fn descendants_postorder_visits_children_before_parents() {
+		let root = prep_nested();
+		let names: Vec<String> = descendants_postorder(&root).map(|el| el.borrow().localname.to_string()).collect();
+		assert_eq!(names, vec!["delay", "message", "forwarded", "body"]);
+	}
+
+	struct RecordingVisitor {
+		elements: Vec<(usize, String)>,
+		texts: Vec<(usize, String)>,
+	}
+
+	impl Visitor for RecordingVisitor {
+		fn visit_element<'a>(&mut self, depth: usize, el: Ref<'a, tree::Element>) {
+			self.elements.push((depth, el.localname.to_string()));
+		}
+
+		fn visit_text(&mut self, depth: usize, text: &str) {
+			self.texts.push((depth, text.to_string()));
+		}
+	}
+
+	#[test]
+	fn walk_visits_elements_and_text_in_document_order_with_depth() {
+		let root = prep_nested();
+		let mut visitor = RecordingVisitor{elements: Vec::new(), texts: Vec::new()};
+		walk(&root, &mut visitor);
+		assert_eq!(visitor.elements, vec![
+			(0, "message".to_string()),
+			(1, "forwarded".to_string()),
+			(2, "delay".to_string()),
+			(2, "message".to_string()),
+			(1, "body".to_string()),
+		]);
+		assert_eq!(visitor.texts, vec![(3, "hi".to_string())]);
+	}
+}
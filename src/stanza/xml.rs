@@ -4,6 +4,7 @@ use std::rc::Rc;
 use std::fmt::Write;
 use rxml::CData;
 use super::tree;
+use super::pp;
 
 const SPECIALS: &'static [char] = &[
 	'"',
@@ -44,14 +45,9 @@ fn attr_escape_value<'a, W>(out: &'a mut W, val: &'a str) -> fmt::Result
 	Ok(())
 }
 
-fn attr_escape<'a, W>(out: &'a mut W, name: &'a str, val: &'a str, nsid: &mut usize) -> fmt::Result
+fn attr_escape<'a, W>(out: &'a mut W, xmlns: Option<&'a str>, name: &'a str, val: &'a str, nsid: &mut usize) -> fmt::Result
 	where W: Write
 {
-	let (xmlns, name) = match name.find('\x01') {
-		Some(offset) => (Some(&name[..offset]), &name[offset+1..]),
-		None => (None, name),
-	};
-
 	if let Some(xmlns) = xmlns {
 		let prefix = format!("prosody-tmp-ns{}", *nsid);
 		*nsid = *nsid + 1;
@@ -69,10 +65,11 @@ pub fn head_as_str<'a>(el: Ref<'a, tree::Element>) -> Result<String, fmt::Error>
 	let mut result = String::new();
 	write!(result, "<{}", el.localname)?;
 	if let Some(nsuri) = el.nsuri.as_ref() {
-		attr_escape(&mut result, "xmlns", nsuri.as_str(), &mut nsid)?;
+		attr_escape(&mut result, None, "xmlns", nsuri.as_str(), &mut nsid)?;
 	}
 	for (k, v) in el.attr.iter() {
-		attr_escape(&mut result, k, v, &mut nsid)?;
+		let (xmlns, localname) = k.decompose();
+		attr_escape(&mut result, xmlns.map(|v| v.as_ref()), localname.as_ref(), v, &mut nsid)?;
 	}
 	result.write_str(">")?;
 	Ok(result)
@@ -81,17 +78,55 @@ pub fn head_as_str<'a>(el: Ref<'a, tree::Element>) -> Result<String, fmt::Error>
 pub struct Formatter {
 	pub indent: Option<String>,
 	pub initial_level: usize,
+	/// When set, render through the width-aware [`pp`] engine instead of
+	/// the plain [`FormatterState`] walk: groups that fit within `max_width`
+	/// columns are kept flat, wider ones break one child per line. `None`
+	/// (the classic behaviour) always breaks every child onto its own line
+	/// whenever [`indent`](Self::indent) is set, regardless of width.
+	pub max_width: Option<usize>,
+}
+
+/// A hook for injecting content around each serialized element, in the
+/// style of rustc `pp.rs`'s `PpAnn`: [`pre`](Self::pre) runs just before
+/// the element's opening `<localname` is emitted, [`post`](Self::post)
+/// just after its closing `</localname>`. Both default to no-ops, so
+/// implementors only override the side they care about. Threaded through
+/// [`Formatter::format_into_with_hook`] (and the `max_width`-aware token
+/// builder); lets callers wrap elements with ANSI color for terminal
+/// dumps, emit routing-debug markers, or similar, without forking the
+/// formatter.
+pub trait SerializeHook {
+	fn pre(&self, _el: &tree::Element, _out: &mut dyn Write) -> fmt::Result { Ok(()) }
+	fn post(&self, _el: &tree::Element, _out: &mut dyn Write) -> fmt::Result { Ok(()) }
 }
 
+/// The no-op [`SerializeHook`] used by [`Formatter::format_into`]/[`Formatter::format`],
+/// so the common path pays nothing for the hook machinery.
+pub struct NoHook;
+
+impl SerializeHook for NoHook {}
+
 struct FormatterState<'a> {
 	formatter: &'a Formatter,
+	hook: &'a dyn SerializeHook,
 	depth: usize,
 	newline: String,
 	parent_ns: Option<Rc<CData>>,
+	/// Namespace-prefix bindings currently in scope, one frame per
+	/// enclosing element (pushed/popped around that element's own
+	/// [`format_el`](Self::format_el) call) -- so a binding introduced by
+	/// an attribute on some element is visible to its descendants and
+	/// goes out of scope once that element is done, same as real XML
+	/// namespace scoping.
+	ns_scopes: Vec<Vec<(String, String)>>,
+	/// Counter for minted `p{N}` prefixes; unlike `attr_escape`'s `nsid`,
+	/// this never resets, so prefixes stay unique across the whole
+	/// document even as scopes are pushed and popped.
+	next_ns_id: usize,
 }
 
 impl<'a> FormatterState<'a> {
-	pub fn new<'b>(formatter: &'b Formatter) -> FormatterState<'b> {
+	pub fn new<'b>(formatter: &'b Formatter, hook: &'b dyn SerializeHook) -> FormatterState<'b> {
 		let level = formatter.initial_level;
 		let level = if level <= 1 {
 			0
@@ -104,9 +139,12 @@ impl<'a> FormatterState<'a> {
 		};
 		FormatterState{
 			formatter: formatter,
+			hook: hook,
 			depth: 0,
 			newline: newline,
 			parent_ns: None,
+			ns_scopes: Vec::new(),
+			next_ns_id: 0,
 		}
 	}
 
@@ -119,6 +157,49 @@ impl<'a> FormatterState<'a> {
 		self.depth -= 1;
 	}
 
+	/// Write a single attribute, resolving a namespaced `name` to a prefix
+	/// already in scope if one is bound for that URI, or minting and
+	/// declaring a fresh one otherwise. Plain unnamespaced attributes are
+	/// written as-is.
+	fn write_attr<'b, W>(&mut self, out: &'b mut W, name: &'b super::attrstr::AttrName, val: &'b str) -> fmt::Result
+		where W: Write
+	{
+		let (xmlns, localname) = name.decompose();
+
+		if let Some(xmlns) = xmlns {
+			let prefix = self.resolve_ns_prefix(out, xmlns.as_ref())?;
+			write!(out, " {}:{}=", prefix, localname)?;
+		} else {
+			write!(out, " {}=", localname)?;
+		}
+		attr_escape_value(out, val)
+	}
+
+	/// Look up `uri` in the currently in-scope [`ns_scopes`](Self::ns_scopes)
+	/// bindings (innermost frame first), reusing its prefix if found;
+	/// otherwise mint a fresh `p{N}` prefix, declare it with a fresh
+	/// `xmlns:p{N}=` attribute, and bind it in the current (innermost)
+	/// frame so descendants can reuse it too.
+	fn resolve_ns_prefix<'b, W>(&mut self, out: &'b mut W, uri: &'b str) -> Result<String, fmt::Error>
+		where W: Write
+	{
+		for frame in self.ns_scopes.iter().rev() {
+			for (bound_uri, prefix) in frame.iter() {
+				if bound_uri == uri {
+					return Ok(prefix.clone());
+				}
+			}
+		}
+		let prefix = format!("p{}", self.next_ns_id);
+		self.next_ns_id += 1;
+		write!(out, " xmlns:{}=", prefix)?;
+		attr_escape_value(out, uri)?;
+		self.ns_scopes.last_mut()
+			.expect("format_el always pushes a scope frame before writing attributes")
+			.push((uri.to_string(), prefix.clone()));
+		Ok(prefix)
+	}
+
 	fn write_indent<'b, W>(&self, indent: &'b String, f: &'b mut W) -> Result<(), fmt::Error>
 		where W: Write
 	{
@@ -139,15 +220,17 @@ impl<'a> FormatterState<'a> {
 	fn format_el<'b, W>(&mut self, el: Ref<'b, tree::Element>, f: &'b mut W) -> Result<(), fmt::Error>
 		where W: Write
 	{
-		write!(f, "<{}", el.localname)?;
+		self.hook.pre(&el, f)?;
+		self.ns_scopes.push(Vec::new());
 		let mut nsid = 0usize;
+		write!(f, "<{}", el.localname)?;
 		let assumed_nsuri = if self.parent_ns != el.nsuri {
 			if let Some(nsuri) = el.nsuri.as_ref() {
-				attr_escape(f, "xmlns", nsuri.as_str(), &mut nsid)?;
+				attr_escape(f, None, "xmlns", nsuri.as_str(), &mut nsid)?;
 				el.nsuri.clone()
 			} else if let Some(nsuri) = self.parent_ns.as_ref() {
 				// XXX: we use the parent uri here, because prosody does not distinguish between that and uses more of a physical representation in its attributes.
-				attr_escape(f, "xmlns", nsuri.as_str(), &mut nsid)?;
+				attr_escape(f, None, "xmlns", nsuri.as_str(), &mut nsid)?;
 				self.parent_ns.clone()
 			} else {
 				None
@@ -156,10 +239,12 @@ impl<'a> FormatterState<'a> {
 			el.nsuri.clone()
 		};
 		for (k, v) in el.attr.iter() {
-			attr_escape(f, k, v, &mut nsid)?;
+			self.write_attr(f, k, v)?;
 		}
 		if el.len() == 0 {
-			return f.write_str("/>")
+			f.write_str("/>")?;
+			self.ns_scopes.pop();
+			return self.hook.post(&el, f);
 		}
 		if let Some(indent) = self.formatter.indent.as_ref() {
 			f.write_str(">")?;
@@ -190,16 +275,112 @@ impl<'a> FormatterState<'a> {
 			}
 		}
 		write!(f, "</{}>", el.localname)?;
-		Ok(())
+		self.ns_scopes.pop();
+		self.hook.post(&el, f)
+	}
+}
+
+/// Build the [`pp::Token`] stream for `el` and its descendants, for the
+/// `max_width`-aware path of [`Formatter::format_into`]. Mirrors
+/// [`FormatterState::format_el`]'s namespace-inheritance and
+/// single-text-child special cases, but defers the flat-vs-broken
+/// decision for each element's children to [`pp::print`] instead of
+/// always breaking.
+fn build_tokens<'b>(el: Ref<'b, tree::Element>, parent_ns: Option<Rc<CData>>, depth: usize, tokens: &mut Vec<pp::Token>, hook: &dyn SerializeHook) -> fmt::Result {
+	let mut head = String::new();
+	hook.pre(&el, &mut head)?;
+	write!(head, "<{}", el.localname)?;
+	let mut nsid = 0usize;
+	let assumed_nsuri = if parent_ns != el.nsuri {
+		if let Some(nsuri) = el.nsuri.as_ref() {
+			attr_escape(&mut head, None, "xmlns", nsuri.as_str(), &mut nsid)?;
+			el.nsuri.clone()
+		} else if let Some(nsuri) = parent_ns.as_ref() {
+			// XXX: see the matching comment in FormatterState::format_el.
+			attr_escape(&mut head, None, "xmlns", nsuri.as_str(), &mut nsid)?;
+			parent_ns.clone()
+		} else {
+			None
+		}
+	} else {
+		el.nsuri.clone()
+	};
+	for (k, v) in el.attr.iter() {
+		let (xmlns, localname) = k.decompose();
+		attr_escape(&mut head, xmlns.map(|v| v.as_ref()), localname.as_ref(), v, &mut nsid)?;
+	}
+
+	if el.len() == 0 {
+		head.write_str("/>")?;
+		hook.post(&el, &mut head)?;
+		tokens.push(pp::Token::Str(head));
+		return Ok(());
+	}
+
+	if el.len() == 1 && el.element_view().len() == 0 {
+		// only single text node, we don't break those
+		head.write_str(">")?;
+		escape(&mut head, el[0].as_text().unwrap())?;
+		write!(head, "</{}>", el.localname)?;
+		hook.post(&el, &mut head)?;
+		tokens.push(pp::Token::Str(head));
+		return Ok(());
+	}
+
+	head.write_str(">")?;
+	tokens.push(pp::Token::Str(head));
+	tokens.push(pp::Token::Begin(pp::BreakStyle::Consistent));
+	for child in el.iter() {
+		if let tree::Node::Text(ref s) = child {
+			// whitespace-only children are ignored
+			if s.find(|c| !char::is_whitespace(c)).is_none() {
+				continue
+			}
+		}
+		tokens.push(pp::Token::Break{blanks: 0, depth: depth + 1});
+		match child {
+			tree::Node::Text(s) => {
+				let mut buf = String::new();
+				escape(&mut buf, s)?;
+				tokens.push(pp::Token::Str(buf));
+			},
+			tree::Node::Element(eptr) => {
+				build_tokens(eptr.borrow(), assumed_nsuri.clone(), depth + 1, tokens, hook)?;
+			},
+		}
 	}
+	tokens.push(pp::Token::End);
+	tokens.push(pp::Token::Break{blanks: 0, depth});
+	let mut tail = format!("</{}>", el.localname);
+	hook.post(&el, &mut tail)?;
+	tokens.push(pp::Token::Str(tail));
+	Ok(())
 }
 
 impl Formatter {
 	pub fn format_into<'a, W>(&self, el: Ref<'a, tree::Element>, f: &'a mut W) -> Result<(), fmt::Error>
 		where W: Write
 	{
-		let mut state = FormatterState::new(self);
-		state.format_el(el, f)
+		self.format_into_with_hook(el, f, &NoHook)
+	}
+
+	/// Like [`format_into`](Self::format_into), but runs `hook` around
+	/// every serialized element's opening/closing tags.
+	pub fn format_into_with_hook<'a, W>(&self, el: Ref<'a, tree::Element>, f: &'a mut W, hook: &dyn SerializeHook) -> Result<(), fmt::Error>
+		where W: Write
+	{
+		match self.max_width {
+			Some(width) => {
+				let mut tokens = Vec::new();
+				build_tokens(el, None, 0, &mut tokens, hook)?;
+				let indent_unit = self.indent.as_deref().unwrap_or("  ");
+				pp::print(&tokens, width, indent_unit, f)
+			},
+			None => {
+				let mut state = FormatterState::new(self, hook);
+				state.format_el(el, f)
+			},
+		}
 	}
 
 	pub fn format<'a>(&self, el: Ref<'a, tree::Element>) -> Result<String, fmt::Error> {
@@ -207,6 +388,14 @@ impl Formatter {
 		self.format_into(el, &mut buf)?;
 		Ok(buf)
 	}
+
+	/// Like [`format`](Self::format), but runs `hook` around every
+	/// serialized element's opening/closing tags.
+	pub fn format_with_hook<'a>(&self, el: Ref<'a, tree::Element>, hook: &dyn SerializeHook) -> Result<String, fmt::Error> {
+		let mut buf = String::new();
+		self.format_into_with_hook(el, &mut buf, hook)?;
+		Ok(buf)
+	}
 }
 
 #[cfg(test)]
@@ -275,25 +464,39 @@ mod tests {
 	fn format_escapes_text_nodes() {
 		let el = tree::ElementPtr::new(None, "foo".try_into().unwrap());
 		el.borrow_mut().text("&bar;<baz/>fnord\"'".try_into().unwrap());
-		let fmt = Formatter{ indent: None, initial_level: 0 };
+		let fmt = Formatter{ indent: None, initial_level: 0, max_width: None };
 		let s = fmt.format(el.borrow()).unwrap();
 		assert_eq!(s, "<foo>&amp;bar;&lt;baz/&gt;fnord&quot;&apos;</foo>");
 	}
 
 	#[test]
 	fn format_escapes_attribute_values() {
-		let mut attr = HashMap::<String, String>::new();
+		let mut attr = HashMap::<crate::stanza::attrstr::AttrName, String>::new();
 		attr.insert("moo".try_into().unwrap(), "&bar;<baz/>fnord\"'".try_into().unwrap());
 		let el = tree::ElementPtr::new_with_attr(None, "foo".try_into().unwrap(), Some(attr));
-		let fmt = Formatter{ indent: None, initial_level: 0 };
+		let fmt = Formatter{ indent: None, initial_level: 0, max_width: None };
 		let s = fmt.format(el.borrow()).unwrap();
 		assert_eq!(s, "<foo moo='&amp;bar;&lt;baz/&gt;fnord&quot;&apos;'/>");
 	}
 
+	#[test]
+	fn format_reuses_namespace_prefix_for_repeated_attribute_uri() {
+		let mut attr = HashMap::<crate::stanza::attrstr::AttrName, String>::new();
+		attr.insert("urn:test\x01a".try_into().unwrap(), "1".to_string());
+		attr.insert("urn:test\x01b".try_into().unwrap(), "2".to_string());
+		let el = tree::ElementPtr::new_with_attr(None, "foo".try_into().unwrap(), Some(attr));
+		let fmt = Formatter{ indent: None, initial_level: 0, max_width: None };
+		let s = fmt.format(el.borrow()).unwrap();
+		// both attributes share the same namespace URI, so the prefix is
+		// declared once and reused, instead of minting one per attribute
+		assert_eq!(s.matches("xmlns:p0=").count(), 1);
+		assert_eq!(s.matches("p0:").count(), 2);
+	}
+
 	#[test]
 	fn format_handles_namespaces() {
-		let ns1 = Rc::new(CData::from_string("uri:foo".try_into().unwrap()).unwrap());
-		let ns2 = Rc::new(CData::from_string("uri:bar".try_into().unwrap()).unwrap());
+		let ns1 = Rc::new(CData::try_from("uri:foo").unwrap());
+		let ns2 = Rc::new(CData::try_from("uri:bar").unwrap());
 		let mut el = tree::ElementPtr::new_with_attr(
 			Some(ns1.clone()),
 			"foo".try_into().unwrap(),
@@ -301,8 +504,58 @@ mod tests {
 		);
 		let c1 = el.borrow_mut().tag(Some(ns1.clone()), "child".try_into().unwrap(), None);
 		let c2 = el.borrow_mut().tag(Some(ns2.clone()), "child".try_into().unwrap(), None);
-		let fmt = Formatter{ indent: None, initial_level: 0 };
+		let fmt = Formatter{ indent: None, initial_level: 0, max_width: None };
 		let s = fmt.format(el.borrow()).unwrap();
 		assert_eq!(s, "<foo xmlns='uri:foo'><child/><child xmlns='uri:bar'/></foo>");
 	}
+
+	#[test]
+	fn format_max_width_keeps_short_tree_flat() {
+		let el = tree::ElementPtr::new(None, "foo".try_into().unwrap());
+		el.borrow_mut().tag(None, "bar".try_into().unwrap(), None);
+		el.borrow_mut().tag(None, "baz".try_into().unwrap(), None);
+		let fmt = Formatter{ indent: Some("  ".to_string()), initial_level: 0, max_width: Some(80) };
+		let s = fmt.format(el.borrow()).unwrap();
+		assert_eq!(s, "<foo><bar/><baz/></foo>");
+	}
+
+	#[test]
+	fn format_max_width_breaks_wide_tree() {
+		let el = tree::ElementPtr::new(None, "foo".try_into().unwrap());
+		el.borrow_mut().tag(None, "bar".try_into().unwrap(), None);
+		el.borrow_mut().tag(None, "baz".try_into().unwrap(), None);
+		let fmt = Formatter{ indent: Some("  ".to_string()), initial_level: 0, max_width: Some(5) };
+		let s = fmt.format(el.borrow()).unwrap();
+		assert_eq!(s, "<foo>\n  <bar/>\n  <baz/>\n</foo>");
+	}
+
+	struct BracketHook;
+
+	impl SerializeHook for BracketHook {
+		fn pre(&self, _el: &tree::Element, out: &mut dyn Write) -> fmt::Result {
+			out.write_str("[")
+		}
+
+		fn post(&self, _el: &tree::Element, out: &mut dyn Write) -> fmt::Result {
+			out.write_str("]")
+		}
+	}
+
+	#[test]
+	fn format_with_hook_wraps_every_element() {
+		let el = tree::ElementPtr::new(None, "foo".try_into().unwrap());
+		el.borrow_mut().tag(None, "bar".try_into().unwrap(), None);
+		let fmt = Formatter{ indent: None, initial_level: 0, max_width: None };
+		let s = fmt.format_with_hook(el.borrow(), &BracketHook).unwrap();
+		assert_eq!(s, "[<foo>[<bar/>]</foo>]");
+	}
+
+	#[test]
+	fn format_with_hook_wraps_every_element_with_max_width() {
+		let el = tree::ElementPtr::new(None, "foo".try_into().unwrap());
+		el.borrow_mut().tag(None, "bar".try_into().unwrap(), None);
+		let fmt = Formatter{ indent: Some("  ".to_string()), initial_level: 0, max_width: Some(80) };
+		let s = fmt.format_with_hook(el.borrow(), &BracketHook).unwrap();
+		assert_eq!(s, "[<foo>[<bar/>]</foo>]");
+	}
 }
@@ -7,46 +7,134 @@ use rxml::CData;
 
 use super::tree;
 use super::attrstr;
+use super::jid::{Jid, JidError};
 
 pub const XMLNS_XMPP_STANZAS: &str = "urn:ietf:params:xml:ns:xmpp-stanzas";
 
-pub fn make_reply<'a>(el: Ref<'a, tree::Element>) -> tree::ElementPtr {
-	let mut attr: HashMap<attrstr::AttrName, String> = HashMap::new();
-	match el.attr.get("id") {
-		Some(v) => {
-			attr.insert("id".try_into().unwrap(), v.clone());
-		},
-		_ => (),
-	};
-	match el.attr.get("from") {
-		Some(v) => {
-			attr.insert("to".try_into().unwrap(), v.clone());
-		},
-		_ => (),
-	};
-	match el.attr.get("to") {
-		Some(v) => {
-			attr.insert("from".try_into().unwrap(), v.clone());
-		},
-		_ => (),
-	};
+/// Parse the `from` attribute of `el` as a [`Jid`], if present.
+pub fn stanza_from<'a>(el: Ref<'a, tree::Element>) -> Option<Result<Jid, JidError>> {
+	el.attr.get("from").map(|s| Jid::parse(s))
+}
+
+/// Parse the `to` attribute of `el` as a [`Jid`], if present.
+pub fn stanza_to<'a>(el: Ref<'a, tree::Element>) -> Option<Result<Jid, JidError>> {
+	el.attr.get("to").map(|s| Jid::parse(s))
+}
+
+/// Reject a stanza whose `to`/`from` attributes, if present, do not parse
+/// as a well-formed [`Jid`]. Intended for use before building a reply, so
+/// that malformed addressing is not silently copied into the reply.
+pub fn validate_stanza_addressing<'a>(el: Ref<'a, tree::Element>) -> Result<(), String> {
+	if let Some(Err(e)) = stanza_from(Ref::clone(&el)) {
+		return Err(format!("malformed from attribute: {}", e));
+	}
+	if let Some(Err(e)) = stanza_to(el) {
+		return Err(format!("malformed to attribute: {}", e));
+	}
+	Ok(())
+}
+
+/// Builds a reply to a stanza, with configurable handling of the
+/// `to`/`from` addressing.
+///
+/// By default, `to`/`from` are swapped verbatim, matching the historical
+/// behaviour of [`make_reply`]. [`bare_from`](Self::bare_from) and
+/// [`bare_to`](Self::bare_to) strip the resourcepart off the respective
+/// address (parsing it as a [`Jid`] first), and
+/// [`explicit_from`](Self::explicit_from) overrides the reply's `from`
+/// with a caller-supplied address instead of deriving it from `el`'s `to`.
+pub struct ReplyBuilder<'a> {
+	el: Ref<'a, tree::Element>,
+	bare_from: bool,
+	bare_to: bool,
+	explicit_from: Option<String>,
+}
+
+impl<'a> ReplyBuilder<'a> {
+	pub fn new(el: Ref<'a, tree::Element>) -> ReplyBuilder<'a> {
+		ReplyBuilder{ el, bare_from: false, bare_to: false, explicit_from: None }
+	}
+
+	/// Bare the reply's `from` address (the original stanza's `to`),
+	/// dropping its resourcepart.
+	pub fn bare_from(mut self, bare: bool) -> Self {
+		self.bare_from = bare;
+		self
+	}
+
+	/// Bare the reply's `to` address (the original stanza's `from`),
+	/// dropping its resourcepart.
+	pub fn bare_to(mut self, bare: bool) -> Self {
+		self.bare_to = bare;
+		self
+	}
+
+	/// Use `from` as the reply's `from` address instead of deriving it
+	/// from the original stanza's `to`.
+	pub fn explicit_from(mut self, from: String) -> Self {
+		self.explicit_from = Some(from);
+		self
+	}
+
+	fn addressed(raw: &str, bare: bool) -> String {
+		if bare {
+			match Jid::parse(raw) {
+				Ok(jid) => jid.bare().to_string(),
+				Err(_) => raw.to_string(),
+			}
+		} else {
+			raw.to_string()
+		}
+	}
 
-	if el.localname == "iq" {
-		attr.insert("type".try_into().unwrap(), "result".to_string());
-	} else {
-		match el.attr.get("type") {
+	pub fn build(self) -> tree::ElementPtr {
+		let el = self.el;
+		let mut attr: HashMap<attrstr::AttrName, String> = HashMap::new();
+		match el.attr.get("id") {
+			Some(v) => {
+				attr.insert("id".try_into().unwrap(), v.clone());
+			},
+			_ => (),
+		};
+		match el.attr.get("from") {
 			Some(v) => {
-				attr.insert("type".try_into().unwrap(), v.clone());
+				attr.insert("to".try_into().unwrap(), Self::addressed(v, self.bare_to));
 			},
 			_ => (),
 		};
+		match self.explicit_from {
+			Some(from) => {
+				attr.insert("from".try_into().unwrap(), from);
+			},
+			None => match el.attr.get("to") {
+				Some(v) => {
+					attr.insert("from".try_into().unwrap(), Self::addressed(v, self.bare_from));
+				},
+				_ => (),
+			},
+		};
+
+		if el.localname == "iq" {
+			attr.insert("type".try_into().unwrap(), "result".to_string());
+		} else {
+			match el.attr.get("type") {
+				Some(v) => {
+					attr.insert("type".try_into().unwrap(), v.clone());
+				},
+				_ => (),
+			};
+		}
+
+		tree::ElementPtr::new_with_attr(
+			None,
+			el.localname.clone(),
+			Some(attr),
+		)
 	}
+}
 
-	tree::ElementPtr::new_with_attr(
-		None,
-		el.localname.clone(),
-		Some(attr),
-	)
+pub fn make_reply<'a>(el: Ref<'a, tree::Element>) -> tree::ElementPtr {
+	ReplyBuilder::new(el).build()
 }
 
 pub struct ElementSelector {
@@ -119,17 +207,297 @@ impl ElementSelector {
 		}
 		None
 	}
+
+	/// Like [`find_first_child`](Self::find_first_child), but runs against
+	/// any iterator of elements rather than just immediate children --
+	/// typically [`walk::descendants`](super::walk::descendants), to find
+	/// the first matching element at any depth.
+	pub fn find_deep<T>(&self, iter: T) -> Option<tree::ElementPtr>
+		where T: Iterator<Item = tree::ElementPtr>
+	{
+		self.find_first_child(iter)
+	}
+
+	/// Like [`find_deep`](Self::find_deep), but collects every matching
+	/// element instead of stopping at the first.
+	pub fn find_all<T>(&self, iter: T) -> Vec<tree::ElementPtr>
+		where T: Iterator<Item = tree::ElementPtr>
+	{
+		let mut result = Vec::new();
+		for el in iter {
+			if self.select(Ref::clone(&el.borrow())) {
+				result.push(el);
+			}
+		}
+		result
+	}
+
+	/// Select the first direct child of `parent` matching `name`/`xmlns`,
+	/// inheriting `parent`'s namespace when `xmlns` is omitted exactly as
+	/// [`select_inside_parent`](Self::select_inside_parent) already does.
+	pub fn get_child<'a>(parent: Ref<'a, tree::Element>, name: Option<&str>, xmlns: Option<Rc<CData>>) -> Option<tree::ElementPtr> {
+		let selector = Self::select_inside_parent(Ref::clone(&parent), name.map(|s| s.to_string()), xmlns);
+		selector.find_first_child(parent.iter_children())
+	}
+
+	/// Like [`get_child`](Self::get_child), but returns the matching
+	/// child's text content rather than the element itself.
+	pub fn get_child_text<'a>(parent: Ref<'a, tree::Element>, name: Option<&str>, xmlns: Option<Rc<CData>>) -> Option<String> {
+		let child = Self::get_child(parent, name, xmlns)?;
+		let child = child.borrow();
+		child.get_text()
+	}
+
+	/// Like [`get_child`](Self::get_child), but returns every matching
+	/// direct child instead of only the first.
+	pub fn get_children<'a>(parent: Ref<'a, tree::Element>, name: Option<&str>, xmlns: Option<Rc<CData>>) -> impl Iterator<Item = tree::ElementPtr> {
+		let selector = Self::select_inside_parent(Ref::clone(&parent), name.map(|s| s.to_string()), xmlns);
+		selector.find_all(parent.iter_children()).into_iter()
+	}
+}
+
+/// Walk `path`, a sequence of `(name, xmlns)` selector steps, starting from
+/// `root`, using [`ElementSelector::get_child`] at each step -- so that an
+/// unspecified namespace at a step inherits the *previous* step's element's
+/// namespace, exactly as a single-level [`ElementSelector`] already does.
+/// Returns the element reached by the last step, or `None` as soon as any
+/// step fails to match.
+pub fn find_path(root: tree::ElementPtr, path: &[(Option<&str>, Option<&Rc<CData>>)]) -> Option<tree::ElementPtr> {
+	let mut current = root;
+	for (name, xmlns) in path {
+		let next = ElementSelector::get_child(current.borrow(), *name, xmlns.cloned())?;
+		current = next;
+	}
+	Some(current)
 }
 
-type ErrorInfo = (String, rxml::Name, Option<String>, Option<tree::ElementPtr>);
+/// The five `<error/>` type values defined by
+/// [RFC 6120 §8.3.2](https://www.rfc-editor.org/rfc/rfc6120#section-8.3.2).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorType {
+	Auth,
+	Cancel,
+	Continue,
+	Modify,
+	Wait,
+}
 
-/// Extract ErrorInfo out of a stanza error.
+impl ErrorType {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Auth => "auth",
+			Self::Cancel => "cancel",
+			Self::Continue => "continue",
+			Self::Modify => "modify",
+			Self::Wait => "wait",
+		}
+	}
+
+	pub fn from_str(s: &str) -> Option<ErrorType> {
+		match s {
+			"auth" => Some(Self::Auth),
+			"cancel" => Some(Self::Cancel),
+			"continue" => Some(Self::Continue),
+			"modify" => Some(Self::Modify),
+			"wait" => Some(Self::Wait),
+			_ => None,
+		}
+	}
+}
+
+/// The defined stanza error conditions from
+/// [RFC 6120 §8.3.3](https://www.rfc-editor.org/rfc/rfc6120#section-8.3.3),
+/// plus [`Other`](Self::Other) for application- or otherwise-undefined
+/// condition elements found in the `urn:ietf:params:xml:ns:xmpp-stanzas`
+/// namespace.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Condition {
+	BadRequest,
+	Conflict,
+	FeatureNotImplemented,
+	Forbidden,
+	Gone,
+	InternalServerError,
+	ItemNotFound,
+	JidMalformed,
+	NotAcceptable,
+	NotAllowed,
+	NotAuthorized,
+	PolicyViolation,
+	RecipientUnavailable,
+	Redirect,
+	RegistrationRequired,
+	RemoteServerNotFound,
+	RemoteServerTimeout,
+	ResourceConstraint,
+	ServiceUnavailable,
+	SubscriptionRequired,
+	UndefinedCondition,
+	UnexpectedRequest,
+	Other(rxml::Name),
+}
+
+impl Condition {
+	pub fn localname(&self) -> &str {
+		match self {
+			Self::BadRequest => "bad-request",
+			Self::Conflict => "conflict",
+			Self::FeatureNotImplemented => "feature-not-implemented",
+			Self::Forbidden => "forbidden",
+			Self::Gone => "gone",
+			Self::InternalServerError => "internal-server-error",
+			Self::ItemNotFound => "item-not-found",
+			Self::JidMalformed => "jid-malformed",
+			Self::NotAcceptable => "not-acceptable",
+			Self::NotAllowed => "not-allowed",
+			Self::NotAuthorized => "not-authorized",
+			Self::PolicyViolation => "policy-violation",
+			Self::RecipientUnavailable => "recipient-unavailable",
+			Self::Redirect => "redirect",
+			Self::RegistrationRequired => "registration-required",
+			Self::RemoteServerNotFound => "remote-server-not-found",
+			Self::RemoteServerTimeout => "remote-server-timeout",
+			Self::ResourceConstraint => "resource-constraint",
+			Self::ServiceUnavailable => "service-unavailable",
+			Self::SubscriptionRequired => "subscription-required",
+			Self::UndefinedCondition => "undefined-condition",
+			Self::UnexpectedRequest => "unexpected-request",
+			Self::Other(name) => name.as_str(),
+		}
+	}
+
+	/// The `type` an `<error/>` carrying this condition should use when
+	/// the caller does not supply one explicitly, per the table in
+	/// RFC 6120 §8.3.3.
+	pub fn default_type(&self) -> ErrorType {
+		match self {
+			Self::BadRequest => ErrorType::Modify,
+			Self::Conflict => ErrorType::Cancel,
+			Self::FeatureNotImplemented => ErrorType::Cancel,
+			Self::Forbidden => ErrorType::Auth,
+			Self::Gone => ErrorType::Cancel,
+			Self::InternalServerError => ErrorType::Wait,
+			Self::ItemNotFound => ErrorType::Cancel,
+			Self::JidMalformed => ErrorType::Modify,
+			Self::NotAcceptable => ErrorType::Modify,
+			Self::NotAllowed => ErrorType::Cancel,
+			Self::NotAuthorized => ErrorType::Auth,
+			Self::PolicyViolation => ErrorType::Modify,
+			Self::RecipientUnavailable => ErrorType::Wait,
+			Self::Redirect => ErrorType::Modify,
+			Self::RegistrationRequired => ErrorType::Auth,
+			Self::RemoteServerNotFound => ErrorType::Cancel,
+			Self::RemoteServerTimeout => ErrorType::Wait,
+			Self::ResourceConstraint => ErrorType::Wait,
+			Self::ServiceUnavailable => ErrorType::Cancel,
+			Self::SubscriptionRequired => ErrorType::Auth,
+			Self::UndefinedCondition => ErrorType::Cancel,
+			Self::UnexpectedRequest => ErrorType::Wait,
+			Self::Other(_) => ErrorType::Cancel,
+		}
+	}
+
+	/// Parse a condition element's localname, falling back to
+	/// [`Other`](Self::Other) for anything not defined by RFC 6120.
+	pub fn from_localname(localname: &rxml::NameStr) -> Condition {
+		match localname.as_ref() {
+			"bad-request" => Self::BadRequest,
+			"conflict" => Self::Conflict,
+			"feature-not-implemented" => Self::FeatureNotImplemented,
+			"forbidden" => Self::Forbidden,
+			"gone" => Self::Gone,
+			"internal-server-error" => Self::InternalServerError,
+			"item-not-found" => Self::ItemNotFound,
+			"jid-malformed" => Self::JidMalformed,
+			"not-acceptable" => Self::NotAcceptable,
+			"not-allowed" => Self::NotAllowed,
+			"not-authorized" => Self::NotAuthorized,
+			"policy-violation" => Self::PolicyViolation,
+			"recipient-unavailable" => Self::RecipientUnavailable,
+			"redirect" => Self::Redirect,
+			"registration-required" => Self::RegistrationRequired,
+			"remote-server-not-found" => Self::RemoteServerNotFound,
+			"remote-server-timeout" => Self::RemoteServerTimeout,
+			"resource-constraint" => Self::ResourceConstraint,
+			"service-unavailable" => Self::ServiceUnavailable,
+			"subscription-required" => Self::SubscriptionRequired,
+			"undefined-condition" => Self::UndefinedCondition,
+			"unexpected-request" => Self::UnexpectedRequest,
+			_ => Self::Other(localname.to_owned()),
+		}
+	}
+
+	/// The [XEP-0086](https://xmpp.org/extensions/xep-0086.html) legacy
+	/// numeric `code` this condition maps to, if any. Used to emit a
+	/// backwards-compatible `code` attribute alongside the modern
+	/// condition element.
+	pub fn legacy_code(&self) -> Option<u16> {
+		Some(match self {
+			Self::Redirect => 302,
+			Self::BadRequest => 400,
+			Self::NotAuthorized => 401,
+			Self::Forbidden => 403,
+			Self::ItemNotFound => 404,
+			Self::NotAllowed => 405,
+			Self::NotAcceptable => 406,
+			Self::RegistrationRequired => 407,
+			Self::Conflict => 409,
+			Self::InternalServerError => 500,
+			Self::FeatureNotImplemented => 501,
+			Self::ServiceUnavailable => 503,
+			Self::RemoteServerTimeout => 504,
+			_ => return None,
+		})
+	}
+}
+
+/// Map a legacy [XEP-0086](https://xmpp.org/extensions/xep-0086.html)
+/// numeric `code` attribute value to the modern condition/type pair it
+/// stands in for.
+fn condition_from_legacy_code(code: u16) -> Option<(Condition, ErrorType)> {
+	Some(match code {
+		302 => (Condition::Redirect, ErrorType::Modify),
+		400 => (Condition::BadRequest, ErrorType::Modify),
+		401 => (Condition::NotAuthorized, ErrorType::Auth),
+		403 => (Condition::Forbidden, ErrorType::Auth),
+		404 => (Condition::ItemNotFound, ErrorType::Cancel),
+		405 => (Condition::NotAllowed, ErrorType::Cancel),
+		406 => (Condition::NotAcceptable, ErrorType::Modify),
+		407 => (Condition::RegistrationRequired, ErrorType::Auth),
+		408 => (Condition::RemoteServerTimeout, ErrorType::Wait),
+		409 => (Condition::Conflict, ErrorType::Cancel),
+		500 => (Condition::InternalServerError, ErrorType::Wait),
+		501 => (Condition::FeatureNotImplemented, ErrorType::Cancel),
+		502 => (Condition::RemoteServerNotFound, ErrorType::Wait),
+		503 => (Condition::ServiceUnavailable, ErrorType::Cancel),
+		504 => (Condition::RemoteServerTimeout, ErrorType::Wait),
+		_ => return None,
+	})
+}
+
+/// A parsed `<error/>` child, with [`type_`](Self::type_) and
+/// [`condition`](Self::condition) resolved to their typed forms instead
+/// of the bare strings/[`rxml::Name`] `extract_error_info` used to hand
+/// back.
+#[derive(Clone, Debug)]
+pub struct StanzaError {
+	pub type_: ErrorType,
+	pub condition: Condition,
+	pub text: Option<String>,
+	pub appdef: Option<tree::ElementPtr>,
+}
+
+/// Extract a [`StanzaError`] out of a stanza error.
 ///
 /// Note that this expects the `<error/>` element as argument, **not** the
 /// parent stanza. Use extract_error for that.
-pub fn extract_error_info<'a>(el: Ref<'a, tree::Element>) -> Option<ErrorInfo> {
-	let type_ = el.attr.get("type")?;
-	let mut condition: rxml::Name = "undefined-condition".try_into().unwrap();
+///
+/// Also recognizes the legacy [XEP-0086](https://xmpp.org/extensions/xep-0086.html)
+/// `code` attribute, using it to fill in the condition and/or type when
+/// the modern condition element is absent and/or the `type` attribute is
+/// absent or not a recognized error type.
+pub fn extract_error_info<'a>(el: Ref<'a, tree::Element>) -> Option<StanzaError> {
+	let mut condition: Option<Condition> = None;
 	let mut text: Option<String> = None;
 	let mut appdef: Option<tree::ElementPtr> = None;
 
@@ -144,7 +512,7 @@ pub fn extract_error_info<'a>(el: Ref<'a, tree::Element>) -> Option<ErrorInfo> {
 							None => None,
 						};
 					} else {
-						condition = child_el.localname.clone();
+						condition = Some(Condition::from_localname(&child_el.localname));
 					}
 				} else {
 					appdef = Some(child_el_ptr.clone());
@@ -154,18 +522,38 @@ pub fn extract_error_info<'a>(el: Ref<'a, tree::Element>) -> Option<ErrorInfo> {
 		}
 	}
 
-	Some((type_.clone(), condition, text, appdef))
+	let legacy = el.attr.get("code")
+		.and_then(|code| code.parse::<u16>().ok())
+		.and_then(condition_from_legacy_code);
+
+	let condition = condition.or_else(|| legacy.as_ref().map(|(c, _)| c.clone()))
+		.unwrap_or(Condition::UndefinedCondition);
+	let type_ = match el.attr.get("type").and_then(|t| ErrorType::from_str(t)) {
+		Some(t) => t,
+		None => legacy.map(|(_, t)| t)?,
+	};
+
+	Some(StanzaError{ type_, condition, text, appdef })
 }
 
 /// Return the error info from the <error/> element inside the given stanza,
 /// if any.
-pub fn extract_error<'a>(st: Ref<'a, tree::Element>) -> Option<ErrorInfo> {
+pub fn extract_error<'a>(st: Ref<'a, tree::Element>) -> Option<StanzaError> {
 	let error_selector = ElementSelector::select_inside_parent(Ref::clone(&st), Some("error".to_string()), None);
 	let error_child = error_selector.find_first_child(st.iter_children())?;
 	extract_error_info(error_child.borrow())
 }
 
-pub fn make_error_reply<'a>(st: Ref<'a, tree::Element>, type_: String, condition: rxml::NCName, text: Option<String>, by: Option<String>) -> Result<tree::ElementPtr, String> {
+/// Build an error reply to `st`.
+///
+/// If `type_` is omitted, it is filled in from
+/// [`condition.default_type()`](Condition::default_type). When
+/// `emit_legacy_code` is set and `condition` has a
+/// [`legacy_code`](Condition::legacy_code), a backwards-compatible
+/// [XEP-0086](https://xmpp.org/extensions/xep-0086.html) `code` attribute
+/// is emitted alongside the modern condition element.
+pub fn make_error_reply<'a>(st: Ref<'a, tree::Element>, type_: Option<ErrorType>, condition: Condition, text: Option<String>, by: Option<String>, emit_legacy_code: bool) -> Result<tree::ElementPtr, String> {
+	validate_stanza_addressing(Ref::clone(&st))?;
 	let reply_ptr = {
 		match st.attr.get("type") {
 			Some(s) => match s.as_str() {
@@ -176,6 +564,7 @@ pub fn make_error_reply<'a>(st: Ref<'a, tree::Element>, type_: String, condition
 		};
 		make_reply(st)
 	};
+	let type_ = type_.unwrap_or_else(|| condition.default_type());
 	{
 		let err_ptr = {
 			let mut reply = reply_ptr.borrow_mut();
@@ -183,16 +572,21 @@ pub fn make_error_reply<'a>(st: Ref<'a, tree::Element>, type_: String, condition
 			reply.tag(None, "error".try_into().unwrap(), None)
 		};
 		let mut err = err_ptr.borrow_mut();
-		err.attr.insert("type".try_into().unwrap(), type_);
+		err.attr.insert("type".try_into().unwrap(), type_.as_str().to_string());
 		match by {
 			Some(by) => { err.attr.insert("by".try_into().unwrap(), by); },
 			_ => (),
 		};
+		if emit_legacy_code {
+			if let Some(code) = condition.legacy_code() {
+				err.attr.insert("code".try_into().unwrap(), code.to_string());
+			}
+		}
 
 		// this is safe because of the staticness of the string
 		let nsuri = Some(Rc::new(unsafe { CData::from_string_unchecked(XMLNS_XMPP_STANZAS.to_string()) }));
 
-		err.tag(nsuri.clone(), condition.into(), None);
+		err.tag(nsuri.clone(), condition.localname().try_into().unwrap(), None);
 		match text {
 			Some(text) => {
 				let text_el_ptr = err.tag(nsuri.clone(), "text".try_into().unwrap(), None);
@@ -271,18 +665,102 @@ mod tests {
 		assert!(!sel.select_str("iq", &mknsuri("jabber:server")));
 	}
 
+	#[test]
+	fn elementselector_find_deep_locates_nested_child() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		let forwarded = root.borrow_mut().tag(None, "forwarded".try_into().unwrap(), None);
+		let delay = forwarded.borrow_mut().tag(None, "delay".try_into().unwrap(), None);
+
+		let sel = ElementSelector::select_inside_xmlns(None, Some("delay".to_string()), None);
+		let found = sel.find_deep(super::super::walk::descendants(&root)).unwrap();
+		assert!(tree::ElementPtr::ptr_eq(&found, &delay));
+	}
+
+	#[test]
+	fn elementselector_find_all_collects_every_match_at_any_depth() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		root.borrow_mut().tag(None, "delay".try_into().unwrap(), None);
+		let forwarded = root.borrow_mut().tag(None, "forwarded".try_into().unwrap(), None);
+		forwarded.borrow_mut().tag(None, "delay".try_into().unwrap(), None);
+
+		let sel = ElementSelector::select_inside_xmlns(None, Some("delay".to_string()), None);
+		let found = sel.find_all(super::super::walk::descendants(&root));
+		assert_eq!(found.len(), 2);
+	}
+
+	#[test]
+	fn get_child_finds_first_direct_child() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		let body = root.borrow_mut().tag(None, "body".try_into().unwrap(), None);
+		let found = ElementSelector::get_child(root.borrow(), Some("body"), None).unwrap();
+		assert!(tree::ElementPtr::ptr_eq(&found, &body));
+	}
+
+	#[test]
+	fn get_child_text_returns_child_text_content() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		let body = root.borrow_mut().tag(None, "body".try_into().unwrap(), None);
+		body.borrow_mut().text("hello".to_string());
+		assert_eq!(ElementSelector::get_child_text(root.borrow(), Some("body"), None).unwrap(), "hello");
+	}
+
+	#[test]
+	fn get_children_collects_every_direct_match() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		root.borrow_mut().tag(None, "x".try_into().unwrap(), None);
+		root.borrow_mut().tag(None, "x".try_into().unwrap(), None);
+		root.borrow_mut().tag(None, "y".try_into().unwrap(), None);
+		let found: Vec<_> = ElementSelector::get_children(root.borrow(), Some("x"), None).collect();
+		assert_eq!(found.len(), 2);
+	}
+
+	#[test]
+	fn find_path_walks_nested_children_inheriting_namespace() {
+		let root = tree::ElementPtr::new(mknsuri("jabber:client"), "message".try_into().unwrap());
+		let result = root.borrow_mut().tag(mknsuri("urn:xmpp:mam:2"), "result".try_into().unwrap(), None);
+		let forwarded = result.borrow_mut().tag(mknsuri("urn:xmpp:forward:0"), "forwarded".try_into().unwrap(), None);
+		let inner_message = forwarded.borrow_mut().tag(mknsuri("jabber:client"), "message".try_into().unwrap(), None);
+		let body = inner_message.borrow_mut().tag(None, "body".try_into().unwrap(), None);
+		body.borrow_mut().text("archived message".to_string());
+
+		let found = find_path(root.clone(), &[
+			(Some("result"), Some(&mknsuri("urn:xmpp:mam:2").unwrap())),
+			(Some("forwarded"), Some(&mknsuri("urn:xmpp:forward:0").unwrap())),
+			(Some("message"), Some(&mknsuri("jabber:client").unwrap())),
+			(Some("body"), None),
+		]).unwrap();
+		assert!(tree::ElementPtr::ptr_eq(&found, &body));
+	}
+
+	#[test]
+	fn find_path_returns_none_when_a_step_does_not_match() {
+		let root = tree::ElementPtr::new(None, "message".try_into().unwrap());
+		root.borrow_mut().tag(None, "forwarded".try_into().unwrap(), None);
+		assert!(find_path(root, &[(Some("nonexistent"), None)]).is_none());
+	}
+
 	#[test]
 	fn extract_error_info_extracts_type_and_defaults_to_undef_condition() {
 		let e = tree::ElementPtr::new_with_attr(
 			None,
 			"error".try_into().unwrap(),
-			Some(mkerrorattr("error type".try_into().unwrap())),
+			Some(mkerrorattr("cancel".try_into().unwrap())),
 		);
-		let (type_, condition, text, extra) = extract_error_info(e.borrow()).unwrap();
-		assert_eq!(type_, "error type");
-		assert_eq!(condition, "undefined-condition");
-		assert!(text.is_none());
-		assert!(extra.is_none());
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::UndefinedCondition);
+		assert!(err.text.is_none());
+		assert!(err.appdef.is_none());
+	}
+
+	#[test]
+	fn extract_error_info_returns_none_for_unrecognized_type() {
+		let e = tree::ElementPtr::new_with_attr(
+			None,
+			"error".try_into().unwrap(),
+			Some(mkerrorattr("not a real type".try_into().unwrap())),
+		);
+		assert!(extract_error_info(e.borrow()).is_none());
 	}
 
 	#[test]
@@ -290,14 +768,26 @@ mod tests {
 		let e = tree::ElementPtr::new_with_attr(
 			None,
 			"error".try_into().unwrap(),
-			Some(mkerrorattr("error type".try_into().unwrap())),
+			Some(mkerrorattr("cancel".try_into().unwrap())),
+		);
+		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "item-not-found".try_into().unwrap(), None);
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::ItemNotFound);
+		assert!(err.text.is_none());
+		assert!(err.appdef.is_none());
+	}
+
+	#[test]
+	fn extract_error_info_maps_unknown_condition_to_other() {
+		let e = tree::ElementPtr::new_with_attr(
+			None,
+			"error".try_into().unwrap(),
+			Some(mkerrorattr("cancel".try_into().unwrap())),
 		);
 		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "random-condition".try_into().unwrap(), None);
-		let (type_, condition, text, extra) = extract_error_info(e.borrow()).unwrap();
-		assert_eq!(type_, "error type");
-		assert_eq!(condition, "random-condition");
-		assert!(text.is_none());
-		assert!(extra.is_none());
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.condition, Condition::Other("random-condition".try_into().unwrap()));
 	}
 
 	#[test]
@@ -305,15 +795,15 @@ mod tests {
 		let e = tree::ElementPtr::new_with_attr(
 			None,
 			"error".try_into().unwrap(),
-			Some(mkerrorattr("error type".try_into().unwrap())),
+			Some(mkerrorattr("cancel".try_into().unwrap())),
 		);
-		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "random-condition".try_into().unwrap(), None);
+		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "item-not-found".try_into().unwrap(), None);
 		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "text".try_into().unwrap(), None).borrow_mut().text("foobar 2342".try_into().unwrap());
-		let (type_, condition, text, extra) = extract_error_info(e.borrow()).unwrap();
-		assert_eq!(type_, "error type");
-		assert_eq!(condition, "random-condition");
-		assert_eq!(text.unwrap(), "foobar 2342");
-		assert!(extra.is_none());
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::ItemNotFound);
+		assert_eq!(err.text.unwrap(), "foobar 2342");
+		assert!(err.appdef.is_none());
 	}
 
 	#[test]
@@ -321,16 +811,30 @@ mod tests {
 		let e = tree::ElementPtr::new_with_attr(
 			None,
 			"error".try_into().unwrap(),
-			Some(mkerrorattr("error type".try_into().unwrap())),
+			Some(mkerrorattr("cancel".try_into().unwrap())),
 		);
-		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "random-condition".try_into().unwrap(), None);
+		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "item-not-found".try_into().unwrap(), None);
 		e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "text".try_into().unwrap(), None).borrow_mut().text("foobar 2342".try_into().unwrap());
 		let appdef_el = e.borrow_mut().tag(mknsuri("urn:uuid:5cf726d1-5be8-44bb-b14a-62880f783ac9"), "appdefcond".try_into().unwrap(), None);
-		let (type_, condition, text, extra) = extract_error_info(e.borrow()).unwrap();
-		assert_eq!(type_, "error type");
-		assert_eq!(condition, "random-condition");
-		assert_eq!(text.unwrap(), "foobar 2342");
-		assert!(tree::ElementPtr::ptr_eq(&extra.unwrap(), &appdef_el));
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::ItemNotFound);
+		assert_eq!(err.text.unwrap(), "foobar 2342");
+		assert!(tree::ElementPtr::ptr_eq(&err.appdef.unwrap(), &appdef_el));
+	}
+
+	#[test]
+	fn extract_error_info_fills_in_condition_and_type_from_legacy_code() {
+		let mut attr = mkerrorattr("ignored".to_string());
+		attr.insert("code".try_into().unwrap(), "404".to_string());
+		let e = tree::ElementPtr::new_with_attr(
+			None,
+			"error".try_into().unwrap(),
+			Some(attr),
+		);
+		let err = extract_error_info(e.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::ItemNotFound);
 	}
 
 	#[test]
@@ -345,11 +849,11 @@ mod tests {
 			e.borrow_mut().tag(mknsuri(XMLNS_XMPP_STANZAS), "remote-server-not-found".try_into().unwrap(), None);
 		}
 
-		let (type_, condition, text, extra) = extract_error(st.borrow()).unwrap();
-		assert_eq!(type_, "wait");
-		assert_eq!(condition, "remote-server-not-found");
-		assert!(text.is_none());
-		assert!(extra.is_none());
+		let err = extract_error(st.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Wait);
+		assert_eq!(err.condition, Condition::RemoteServerNotFound);
+		assert!(err.text.is_none());
+		assert!(err.appdef.is_none());
 	}
 
 	#[test]
@@ -359,13 +863,40 @@ mod tests {
 			"message".try_into().unwrap(),
 			None,
 		);
-		let reply = make_error_reply(st.borrow(), "cancel".try_into().unwrap(), "undefined-condition".try_into().unwrap(), None, None);
+		let reply = make_error_reply(st.borrow(), Some(ErrorType::Cancel), Condition::UndefinedCondition, None, None, false);
 		assert!(reply.is_ok());
 		let reply = reply.unwrap();
 		assert_eq!(reply.borrow().attr.get("type").unwrap(), "error");
 		assert_eq!(reply.borrow()[0].as_element_ptr().unwrap().borrow().attr.get("type").unwrap(), "cancel");
 	}
 
+	#[test]
+	fn make_error_reply_fills_in_default_type_when_omitted() {
+		let st = tree::ElementPtr::new_with_attr(
+			None,
+			"message".try_into().unwrap(),
+			None,
+		);
+		let reply = make_error_reply(st.borrow(), None, Condition::NotAuthorized, None, None, false);
+		assert!(reply.is_ok());
+		let reply = reply.unwrap();
+		assert_eq!(reply.borrow()[0].as_element_ptr().unwrap().borrow().attr.get("type").unwrap(), "auth");
+	}
+
+	#[test]
+	fn make_error_reply_can_emit_legacy_code() {
+		let st = tree::ElementPtr::new_with_attr(
+			None,
+			"message".try_into().unwrap(),
+			None,
+		);
+		let reply = make_error_reply(st.borrow(), None, Condition::ItemNotFound, None, None, true);
+		assert!(reply.is_ok());
+		let reply = reply.unwrap();
+		let err_el = reply.borrow()[0].as_element_ptr().unwrap();
+		assert_eq!(err_el.borrow().attr.get("code").unwrap(), "404");
+	}
+
 	#[test]
 	fn extract_error_can_extract_from_make_error_reply_result() {
 		let st = tree::ElementPtr::new_with_attr(
@@ -373,14 +904,14 @@ mod tests {
 			"message".try_into().unwrap(),
 			None,
 		);
-		let reply = make_error_reply(st.borrow(), "cancel".try_into().unwrap(), "some-condition".try_into().unwrap(), Some("error text".try_into().unwrap()), Some("origin".try_into().unwrap()));
+		let reply = make_error_reply(st.borrow(), Some(ErrorType::Cancel), Condition::Other("some-condition".try_into().unwrap()), Some("error text".try_into().unwrap()), Some("origin".try_into().unwrap()), false);
 		assert!(reply.is_ok());
-		let (type_, condition, text, extra) = extract_error(reply.unwrap().borrow()).unwrap();
-		assert_eq!(type_, "cancel");
-		assert_eq!(condition, "some-condition");
-		assert!(text.is_some());
-		assert_eq!(text.unwrap(), "error text");
-		assert!(extra.is_none());
+		let err = extract_error(reply.unwrap().borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::Other("some-condition".try_into().unwrap()));
+		assert!(err.text.is_some());
+		assert_eq!(err.text.unwrap(), "error text");
+		assert!(err.appdef.is_none());
 	}
 
 	#[test]
@@ -390,7 +921,7 @@ mod tests {
 			"message".try_into().unwrap(),
 			None,
 		);
-		let reply = make_error_reply(st.borrow(), "cancel".try_into().unwrap(), "some-condition".try_into().unwrap(), Some("error text".try_into().unwrap()), Some("origin".try_into().unwrap()));
+		let reply = make_error_reply(st.borrow(), Some(ErrorType::Cancel), Condition::Other("some-condition".try_into().unwrap()), Some("error text".try_into().unwrap()), Some("origin".try_into().unwrap()), false);
 		assert!(reply.is_ok());
 		let reply = reply.unwrap();
 		let custom_condition = {
@@ -401,12 +932,66 @@ mod tests {
 			);
 			custom_el_ptr
 		};
-		let (type_, condition, text, extra) = extract_error(reply.borrow()).unwrap();
-		assert_eq!(type_, "cancel");
-		assert_eq!(condition, "some-condition");
-		assert!(text.is_some());
-		assert_eq!(text.unwrap(), "error text");
-		assert!(extra.is_some());
-		assert!(tree::ElementPtr::ptr_eq(&extra.unwrap(), &custom_condition));
+		let err = extract_error(reply.borrow()).unwrap();
+		assert_eq!(err.type_, ErrorType::Cancel);
+		assert_eq!(err.condition, Condition::Other("some-condition".try_into().unwrap()));
+		assert!(err.text.is_some());
+		assert_eq!(err.text.unwrap(), "error text");
+		assert!(err.appdef.is_some());
+		assert!(tree::ElementPtr::ptr_eq(&err.appdef.unwrap(), &custom_condition));
+	}
+
+	fn mkmessage(from: Option<&str>, to: Option<&str>) -> tree::ElementPtr {
+		let mut attr = HashMap::new();
+		if let Some(from) = from {
+			attr.insert("from".try_into().unwrap(), from.to_string());
+		}
+		if let Some(to) = to {
+			attr.insert("to".try_into().unwrap(), to.to_string());
+		}
+		tree::ElementPtr::new_with_attr(None, "message".try_into().unwrap(), Some(attr))
+	}
+
+	#[test]
+	fn make_reply_swaps_to_and_from_verbatim() {
+		let st = mkmessage(Some("romeo@montague.lit/orchard"), Some("juliet@capulet.lit/balcony"));
+		let reply = make_reply(st.borrow());
+		assert_eq!(reply.borrow().attr.get("to").unwrap(), "romeo@montague.lit/orchard");
+		assert_eq!(reply.borrow().attr.get("from").unwrap(), "juliet@capulet.lit/balcony");
+	}
+
+	#[test]
+	fn reply_builder_can_bare_to_and_from() {
+		let st = mkmessage(Some("romeo@montague.lit/orchard"), Some("juliet@capulet.lit/balcony"));
+		let reply = ReplyBuilder::new(st.borrow()).bare_to(true).bare_from(true).build();
+		assert_eq!(reply.borrow().attr.get("to").unwrap(), "romeo@montague.lit");
+		assert_eq!(reply.borrow().attr.get("from").unwrap(), "juliet@capulet.lit");
+	}
+
+	#[test]
+	fn reply_builder_can_set_explicit_from() {
+		let st = mkmessage(Some("romeo@montague.lit/orchard"), Some("juliet@capulet.lit/balcony"));
+		let reply = ReplyBuilder::new(st.borrow()).explicit_from("muc.capulet.lit".to_string()).build();
+		assert_eq!(reply.borrow().attr.get("from").unwrap(), "muc.capulet.lit");
+	}
+
+	#[test]
+	fn stanza_from_and_to_parse_jids() {
+		let st = mkmessage(Some("romeo@montague.lit/orchard"), Some("juliet@capulet.lit"));
+		assert_eq!(stanza_from(st.borrow()).unwrap().unwrap(), Jid::parse("romeo@montague.lit/orchard").unwrap());
+		assert_eq!(stanza_to(st.borrow()).unwrap().unwrap(), Jid::parse("juliet@capulet.lit").unwrap());
+	}
+
+	#[test]
+	fn validate_stanza_addressing_rejects_malformed_from() {
+		let st = mkmessage(Some("@montague.lit"), None);
+		assert!(validate_stanza_addressing(st.borrow()).is_err());
+	}
+
+	#[test]
+	fn make_error_reply_rejects_malformed_addressing() {
+		let st = mkmessage(Some("@montague.lit"), None);
+		let reply = make_error_reply(st.borrow(), Some(ErrorType::Cancel), Condition::UndefinedCondition, None, None, false);
+		assert!(reply.is_err());
 	}
 }
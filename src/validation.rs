@@ -1,22 +1,25 @@
-use bstr::BStr;
+use bstr::{BStr, ByteSlice};
 use std::fmt;
+use std::ops::Deref;
+use std::convert::TryFrom;
 
 #[derive(PartialEq, Debug)]
 pub enum TextConstraintError {
 	EmptyString,
 	InvalidCharacter(usize, char),
 	InvalidUtf8,
+	UnexpectedColon(usize),
+	RestrictedCharacter(usize, char),
 }
 
-// TODO: use a struct wrapper around String in order to typesafely check that
-// strings conform to certain requirements?
-
 impl fmt::Display for TextConstraintError {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
 			TextConstraintError::InvalidUtf8 => write!(f, "invalid utf8"),
 			TextConstraintError::InvalidCharacter(pos, _) => write!(f, "invalid character at position {}", pos),
 			TextConstraintError::EmptyString => write!(f, "empty string"),
+			TextConstraintError::UnexpectedColon(pos) => write!(f, "unexpected ':' at position {} (NCName must not contain a colon)", pos),
+			TextConstraintError::RestrictedCharacter(pos, _) => write!(f, "restricted character at position {} (only legal as a numeric character reference)", pos),
 		}
 	}
 }
@@ -43,6 +46,36 @@ pub fn convert_xml_attribute_name(raw: &BStr) -> Result<String, TextConstraintEr
 	}
 }
 
+/// Like [`convert_xml_element_name`], but enforces the XML Namespaces
+/// `NCName` rule (no colons anywhere in the local part) instead of
+/// XML 1.0's plain `Name`, for callers that need to reject malformed
+/// namespaced local names/prefixes at construction time rather than
+/// emitting unparseable XML downstream.
+pub fn convert_xml_ncname_element_name(raw: &BStr) -> Result<String, TextConstraintError> {
+	let s = match String::from_utf8(raw.to_vec()) {
+		Ok(s) => s,
+		Err(_) => return Err(TextConstraintError::InvalidUtf8),
+	};
+	match check_xml_ncname(s.as_str(), false) {
+		Some(err) => Err(err),
+		None => Ok(s),
+	}
+}
+
+/// Like [`convert_xml_attribute_name`], but enforces the `NCName` rule,
+/// while still allowing prosody's `xmlns\x01attribute-name` internal
+/// encoding (see [`check_xml_ncname`]).
+pub fn convert_xml_ncname_attribute_name(raw: &BStr) -> Result<String, TextConstraintError> {
+	let s = match String::from_utf8(raw.to_vec()) {
+		Ok(s) => s,
+		Err(_) => return Err(TextConstraintError::InvalidUtf8),
+	};
+	match check_xml_ncname(s.as_str(), true) {
+		Some(err) => Err(err),
+		None => Ok(s),
+	}
+}
+
 pub fn convert_xml_cdata(raw: &BStr) -> Result<String, TextConstraintError> {
 	let s = match String::from_utf8(raw.to_vec()) {
 		Ok(s) => s,
@@ -54,6 +87,215 @@ pub fn convert_xml_cdata(raw: &BStr) -> Result<String, TextConstraintError> {
 	}
 }
 
+/// How [`sanitize_xml_cdata`] should handle a character that
+/// [`check_valid_cdata_char`] rejects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidCharPolicy {
+	/// Fail outright on the first invalid character -- the historical
+	/// behavior of [`convert_xml_cdata`].
+	Reject,
+	/// Silently strip invalid codepoints from the output.
+	Drop,
+	/// Substitute each invalid codepoint with the given replacement
+	/// character (e.g. `'\u{fffd}'`).
+	ReplaceWith(char),
+}
+
+/// The result of a non-[`Reject`](InvalidCharPolicy::Reject)
+/// [`sanitize_xml_cdata`] call: the cleaned text, and the char-indices
+/// (into the *original* string) of every codepoint that was dropped or
+/// replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizedCData {
+	pub text: String,
+	pub altered: Vec<usize>,
+}
+
+/// Like [`convert_xml_cdata`], but instead of always failing on the
+/// first invalid character, lets the caller opt into a lossy-but-always-
+/// succeeding `policy` -- useful for real-world message bodies that may
+/// carry a stray control character, where dropping the whole stanza is
+/// worse than dropping or substituting the offending codepoint.
+/// [`InvalidCharPolicy::Reject`] still fails, for parity with
+/// [`convert_xml_cdata`].
+pub fn sanitize_xml_cdata(raw: &BStr, policy: InvalidCharPolicy) -> Result<SanitizedCData, TextConstraintError> {
+	let s = match String::from_utf8(raw.to_vec()) {
+		Ok(s) => s,
+		Err(_) => return Err(TextConstraintError::InvalidUtf8),
+	};
+	let mut text = String::with_capacity(s.len());
+	let mut altered = Vec::new();
+	for (i, codepoint) in s.chars().enumerate() {
+		if check_valid_cdata_char(codepoint) {
+			text.push(codepoint);
+			continue;
+		}
+		match policy {
+			InvalidCharPolicy::Reject => return Err(TextConstraintError::InvalidCharacter(i, codepoint)),
+			InvalidCharPolicy::Drop => altered.push(i),
+			InvalidCharPolicy::ReplaceWith(replacement) => {
+				text.push(replacement);
+				altered.push(i);
+			},
+		}
+	}
+	Ok(SanitizedCData { text, altered })
+}
+
+/// A `String` that has already passed [`convert_xml_element_name`].
+/// Can only be constructed through `TryFrom`, or via
+/// [`ElementName::new_unchecked`] for the fast path where the caller
+/// (e.g. prosody itself, or a round-trip from an already-validated
+/// [`Element`](crate::stanza::tree::Element)) has already guaranteed validity --
+/// moving the check to the type boundary instead of re-validating at
+/// serialization time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ElementName(String);
+
+impl ElementName {
+	/// Wrap `s` without validating it.
+	pub fn new_unchecked(s: String) -> ElementName {
+		ElementName(s)
+	}
+
+	pub fn into_inner(self) -> String {
+		self.0
+	}
+}
+
+impl TryFrom<&BStr> for ElementName {
+	type Error = TextConstraintError;
+
+	fn try_from(raw: &BStr) -> Result<Self, Self::Error> {
+		convert_xml_element_name(raw).map(ElementName)
+	}
+}
+
+impl TryFrom<String> for ElementName {
+	type Error = TextConstraintError;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		match check_xml_element_name(s.as_str(), false) {
+			Some(err) => Err(err),
+			None => Ok(ElementName(s)),
+		}
+	}
+}
+
+impl Deref for ElementName {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		self.0.as_str()
+	}
+}
+
+/// A `String` that has already passed [`convert_xml_attribute_name`].
+/// See [`ElementName`] for the general rationale; [`AttributeName::new_unchecked`]
+/// is the fast-path escape hatch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AttributeName(String);
+
+impl AttributeName {
+	/// Wrap `s` without validating it.
+	pub fn new_unchecked(s: String) -> AttributeName {
+		AttributeName(s)
+	}
+
+	pub fn into_inner(self) -> String {
+		self.0
+	}
+}
+
+impl TryFrom<&BStr> for AttributeName {
+	type Error = TextConstraintError;
+
+	fn try_from(raw: &BStr) -> Result<Self, Self::Error> {
+		convert_xml_attribute_name(raw).map(AttributeName)
+	}
+}
+
+impl TryFrom<String> for AttributeName {
+	type Error = TextConstraintError;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		match check_xml_element_name(s.as_str(), true) {
+			Some(err) => Err(err),
+			None => Ok(AttributeName(s)),
+		}
+	}
+}
+
+impl Deref for AttributeName {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		self.0.as_str()
+	}
+}
+
+/// A `String` that has already passed [`convert_xml_cdata`]. See
+/// [`ElementName`] for the general rationale; [`CData::new_unchecked`]
+/// is the fast-path escape hatch.
+///
+/// Unrelated to [`rxml::CData`] despite the shared name -- this one
+/// wraps a whole text node's worth of already-escaped-free content,
+/// validated against XML 1.0 § 2.2, rather than rxml's wire-format type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CData(String);
+
+impl CData {
+	/// Wrap `s` without validating it.
+	pub fn new_unchecked(s: String) -> CData {
+		CData(s)
+	}
+
+	pub fn into_inner(self) -> String {
+		self.0
+	}
+}
+
+impl TryFrom<&BStr> for CData {
+	type Error = TextConstraintError;
+
+	fn try_from(raw: &BStr) -> Result<Self, Self::Error> {
+		convert_xml_cdata(raw).map(CData)
+	}
+}
+
+impl TryFrom<String> for CData {
+	type Error = TextConstraintError;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		match check_xml_cdata(s.as_str()) {
+			Some(err) => Err(err),
+			None => Ok(CData(s)),
+		}
+	}
+}
+
+impl Deref for CData {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		self.0.as_str()
+	}
+}
+
+/// Which revision of the XML character model to validate against --
+/// [`check_valid_cdata_char_for_version`] and friends switch table and
+/// behavior based on this. XML 1.1 (§ 2.2) broadens the set of
+/// characters a document may contain: most of the C0 control range,
+/// forbidden outright in 1.0, becomes *restricted* -- legal only when
+/// written as a numeric character reference (`&#x1;`) rather than
+/// literally -- which is why [`TextConstraintError::RestrictedCharacter`]
+/// exists as a distinct case from [`TextConstraintError::InvalidCharacter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlVersion {
+	Xml10,
+	Xml11,
+}
+
 // start to end (incl., because some of our edge points are not valid chars
 // in rust)
 struct CodepointRange(char, char);
@@ -67,6 +309,29 @@ const VALID_XML_CDATA_RANGES: &'static [CodepointRange] = &[
 	CodepointRange('\u{10000}', '\u{10ffff}'),
 ];
 
+// XML 1.1 § 2.2 `Char`: nearly all of Unicode, minus #x0, the
+// surrogate block, and #xFFFE/#xFFFF -- notably including the C0/C1
+// controls that 1.0 forbids outright. Those controls are still
+// *restricted* (see `RESTRICTED_XML11_CHAR_RANGES`), but no longer
+// rejected by this table; restriction is checked separately so callers
+// can tell "not a character at all" apart from "legal only by
+// reference".
+const VALID_XML11_CDATA_RANGES: &'static [CodepointRange] = &[
+	CodepointRange('\u{0001}', '\u{d7ff}'),
+	CodepointRange('\u{e000}', '\u{fffd}'),
+	CodepointRange('\u{10000}', '\u{10ffff}'),
+];
+
+// XML 1.1 § 2.2 `RestrictedChar`: legal in a 1.1 document only as a
+// numeric character reference, never literally.
+const RESTRICTED_XML11_CHAR_RANGES: &'static [CodepointRange] = &[
+	CodepointRange('\u{0001}', '\u{0008}'),
+	CodepointRange('\u{000b}', '\u{000c}'),
+	CodepointRange('\u{000e}', '\u{001f}'),
+	CodepointRange('\u{007f}', '\u{0084}'),
+	CodepointRange('\u{0086}', '\u{009f}'),
+];
+
 
 // XML 1.0 § 2.3 [4]
 const VALID_XML_NAME_START_RANGES: &'static [CodepointRange] = &[
@@ -89,13 +354,15 @@ const VALID_XML_NAME_START_RANGES: &'static [CodepointRange] = &[
 
 
 // XML 1.0 § 2.3 [4a]
+// NOTE: must remain sorted by lower bound and non-overlapping; see
+// `in_ranges` and the `*_ranges_are_sorted_and_disjoint` tests below.
 const VALID_XML_NAME_RANGES: &'static [CodepointRange] = &[
-	CodepointRange(':', ':'),
 	CodepointRange('-', '-'),
 	CodepointRange('.', '.'),
+	CodepointRange('0', '9'),
+	CodepointRange(':', ':'),
 	CodepointRange('A', 'Z'),
 	CodepointRange('_', '_'),
-	CodepointRange('0', '9'),
 	CodepointRange('a', 'z'),
 	CodepointRange('\u{b7}', '\u{b7}'),
 	CodepointRange('\u{c0}', '\u{d6}'),
@@ -119,31 +386,104 @@ impl CodepointRange {
 	}
 }
 
-pub fn check_valid_cdata_char(c: char) -> bool {
-	for range in VALID_XML_CDATA_RANGES.iter() {
-		if range.contains(c) {
+/// Binary-search `c` against `table`, analogous to libcore's
+/// `bsearch_range_table`. Requires `table` to be sorted by lower bound
+/// and non-overlapping, which the `VALID_XML_*_RANGES` tables are
+/// guaranteed to be by the `*_ranges_are_sorted_and_disjoint` tests
+/// below -- this turns each per-character check from O(n) to O(log n),
+/// which matters since it runs on the hot path of validating and
+/// serializing every stanza name and text node.
+fn in_ranges(c: char, table: &[CodepointRange]) -> bool {
+	let (mut lo, mut hi) = (0, table.len());
+	while lo < hi {
+		let mid = (lo + hi) / 2;
+		let r = &table[mid];
+		if c < r.0 {
+			hi = mid;
+		} else if c > r.1 {
+			lo = mid + 1;
+		} else {
 			return true;
 		}
 	}
-	return false;
+	false
 }
 
-pub fn check_valid_name_start_char(c: char) -> bool {
-	for range in VALID_XML_NAME_START_RANGES.iter() {
-		if range.contains(c) {
-			return true;
+fn ranges_are_sorted_and_disjoint(table: &[CodepointRange]) -> bool {
+	table.windows(2).all(|pair| pair[0].1 < pair[1].0)
+}
+
+pub fn check_valid_cdata_char(c: char) -> bool {
+	debug_assert!(ranges_are_sorted_and_disjoint(VALID_XML_CDATA_RANGES));
+	in_ranges(c, VALID_XML_CDATA_RANGES)
+}
+
+/// Like [`check_valid_cdata_char`], but checks against the character
+/// model of `version` -- returning `false` for a restricted XML 1.1
+/// character too, since a bare bool can't distinguish "forbidden
+/// outright" from "only legal as a reference". Use
+/// [`check_xml11_restricted_char`] to tell those apart.
+pub fn check_valid_cdata_char_for_version(c: char, version: XmlVersion) -> bool {
+	match version {
+		XmlVersion::Xml10 => check_valid_cdata_char(c),
+		XmlVersion::Xml11 => {
+			debug_assert!(ranges_are_sorted_and_disjoint(VALID_XML11_CDATA_RANGES));
+			in_ranges(c, VALID_XML11_CDATA_RANGES) && !check_xml11_restricted_char(c)
 		}
 	}
-	return false;
+}
+
+/// Whether `c` is an XML 1.1 `RestrictedChar` -- legal in an XML 1.1
+/// document only as a numeric character reference, never literally.
+/// Meaningless for XML 1.0, which forbids these characters outright.
+pub fn check_xml11_restricted_char(c: char) -> bool {
+	debug_assert!(ranges_are_sorted_and_disjoint(RESTRICTED_XML11_CHAR_RANGES));
+	in_ranges(c, RESTRICTED_XML11_CHAR_RANGES)
+}
+
+pub fn check_valid_name_start_char(c: char) -> bool {
+	debug_assert!(ranges_are_sorted_and_disjoint(VALID_XML_NAME_START_RANGES));
+	in_ranges(c, VALID_XML_NAME_START_RANGES)
 }
 
 pub fn check_valid_name_char(c: char) -> bool {
-	for range in VALID_XML_NAME_RANGES.iter() {
-		if range.contains(c) {
-			return true;
+	debug_assert!(ranges_are_sorted_and_disjoint(VALID_XML_NAME_RANGES));
+	in_ranges(c, VALID_XML_NAME_RANGES)
+}
+
+/// Like [`check_xml_element_name`], but enforces the XML Namespaces
+/// `NCName` production (a `Name` with no colons) instead of plain
+/// XML 1.0 `Name` -- required for a local name or prefix to be valid
+/// once namespaces are in play. `attribute_hack`, as in
+/// [`check_xml_element_name`], still allows prosody's internal
+/// `xmlns\x01attribute-name` encoding to pass through unscathed.
+pub fn check_xml_ncname(s: &str, attribute_hack: bool) -> Option<TextConstraintError> {
+	let mut iterator = s.chars().enumerate();
+	let (i, codepoint) = match iterator.next() {
+		Some((i, codepoint)) => (i, codepoint),
+		None => return Some(TextConstraintError::EmptyString),
+	};
+	if codepoint == ':' {
+		return Some(TextConstraintError::UnexpectedColon(i));
+	}
+	if !check_valid_name_start_char(codepoint) {
+		return Some(TextConstraintError::InvalidCharacter(i, codepoint));
+	}
+
+	for (i, codepoint) in iterator {
+		if attribute_hack && codepoint == '\x01' {
+			// prosody uses `xmlns\x01attribute-name` as internal storage for namespaced attributes... don’t ask me.
+			continue
+		}
+
+		if codepoint == ':' {
+			return Some(TextConstraintError::UnexpectedColon(i));
+		}
+		if !check_valid_name_char(codepoint) {
+			return Some(TextConstraintError::InvalidCharacter(i, codepoint));
 		}
 	}
-	return false;
+	None
 }
 
 pub fn check_xml_element_name(s: &str, attribute_hack: bool) -> Option<TextConstraintError> {
@@ -178,6 +518,34 @@ pub fn check_xml_cdata(s: &str) -> Option<TextConstraintError> {
 	None
 }
 
+/// Like [`check_xml_cdata`], but validates against `version`'s
+/// character model, distinguishing an XML 1.1 restricted character
+/// (legal only by reference) from one that is invalid outright.
+pub fn check_xml_cdata_for_version(s: &str, version: XmlVersion) -> Option<TextConstraintError> {
+	for (i, codepoint) in s.chars().enumerate() {
+		if version == XmlVersion::Xml11 && check_xml11_restricted_char(codepoint) {
+			return Some(TextConstraintError::RestrictedCharacter(i, codepoint));
+		}
+		if !check_valid_cdata_char_for_version(codepoint, version) {
+			return Some(TextConstraintError::InvalidCharacter(i, codepoint));
+		}
+	}
+	None
+}
+
+/// Like [`convert_xml_cdata`], but validates against `version`'s
+/// character model via [`check_xml_cdata_for_version`].
+pub fn convert_xml_cdata_for_version(raw: &BStr, version: XmlVersion) -> Result<String, TextConstraintError> {
+	let s = match String::from_utf8(raw.to_vec()) {
+		Ok(s) => s,
+		Err(_) => return Err(TextConstraintError::InvalidUtf8),
+	};
+	match check_xml_cdata_for_version(s.as_str(), version) {
+		Some(err) => Err(err),
+		None => Ok(s),
+	}
+}
+
 #[cfg(test)]
 #[test]
 fn reject_empty_string_for_elements() {
@@ -188,3 +556,345 @@ fn reject_empty_string_for_elements() {
 fn reject_element_name_starting_with_number() {
 	assert_eq!(check_xml_element_name("0foo", false), Some(TextConstraintError::InvalidCharacter(0, '0')));
 }
+
+#[cfg(test)]
+#[test]
+fn cdata_ranges_are_sorted_and_disjoint() {
+	assert!(ranges_are_sorted_and_disjoint(VALID_XML_CDATA_RANGES));
+}
+
+#[cfg(test)]
+#[test]
+fn name_start_ranges_are_sorted_and_disjoint() {
+	assert!(ranges_are_sorted_and_disjoint(VALID_XML_NAME_START_RANGES));
+}
+
+#[cfg(test)]
+#[test]
+fn name_ranges_are_sorted_and_disjoint() {
+	assert!(ranges_are_sorted_and_disjoint(VALID_XML_NAME_RANGES));
+}
+
+#[cfg(test)]
+#[test]
+fn accept_valid_ncname() {
+	assert_eq!(check_xml_ncname("foo-bar", false), None);
+}
+
+#[cfg(test)]
+#[test]
+fn reject_ncname_with_colon() {
+	assert_eq!(check_xml_ncname("foo:bar", false), Some(TextConstraintError::UnexpectedColon(3)));
+}
+
+#[cfg(test)]
+#[test]
+fn reject_ncname_starting_with_colon() {
+	assert_eq!(check_xml_ncname(":foo", false), Some(TextConstraintError::UnexpectedColon(0)));
+}
+
+#[cfg(test)]
+#[test]
+fn ncname_attribute_hack_still_allows_internal_separator() {
+	assert_eq!(check_xml_ncname("xmlns\x01attr", true), None);
+}
+
+#[cfg(test)]
+#[test]
+fn element_name_try_from_rejects_invalid_name() {
+	let result = ElementName::try_from("0foo".to_string());
+	assert_eq!(result, Err(TextConstraintError::InvalidCharacter(0, '0')));
+}
+
+#[cfg(test)]
+#[test]
+fn element_name_try_from_accepts_valid_name() {
+	let name = ElementName::try_from("foo".to_string()).unwrap();
+	assert_eq!(&*name, "foo");
+}
+
+#[cfg(test)]
+#[test]
+fn element_name_new_unchecked_bypasses_validation() {
+	let name = ElementName::new_unchecked("0foo".to_string());
+	assert_eq!(&*name, "0foo");
+}
+
+#[cfg(test)]
+#[test]
+fn attribute_name_try_from_allows_internal_separator() {
+	let name = AttributeName::try_from("xmlns\x01attr".to_string()).unwrap();
+	assert_eq!(&*name, "xmlns\x01attr");
+}
+
+#[cfg(test)]
+#[test]
+fn cdata_try_from_rejects_invalid_char() {
+	let result = CData::try_from("bad\x00char".to_string());
+	assert_eq!(result, Err(TextConstraintError::InvalidCharacter(3, '\x00')));
+}
+
+#[cfg(test)]
+#[test]
+fn xml11_cdata_ranges_are_sorted_and_disjoint() {
+	assert!(ranges_are_sorted_and_disjoint(VALID_XML11_CDATA_RANGES));
+}
+
+#[cfg(test)]
+#[test]
+fn xml11_restricted_char_ranges_are_sorted_and_disjoint() {
+	assert!(ranges_are_sorted_and_disjoint(RESTRICTED_XML11_CHAR_RANGES));
+}
+
+#[cfg(test)]
+#[test]
+fn xml10_rejects_c0_control_outright() {
+	assert_eq!(check_xml_cdata_for_version("a\x01b", XmlVersion::Xml10), Some(TextConstraintError::InvalidCharacter(1, '\x01')));
+}
+
+#[cfg(test)]
+#[test]
+fn xml11_flags_c0_control_as_restricted_not_invalid() {
+	assert_eq!(check_xml_cdata_for_version("a\x01b", XmlVersion::Xml11), Some(TextConstraintError::RestrictedCharacter(1, '\x01')));
+}
+
+#[cfg(test)]
+#[test]
+fn xml11_still_rejects_null_byte_outright() {
+	assert_eq!(check_xml_cdata_for_version("a\x00b", XmlVersion::Xml11), Some(TextConstraintError::InvalidCharacter(1, '\x00')));
+}
+
+#[cfg(test)]
+#[test]
+fn xml11_accepts_ordinary_text() {
+	assert_eq!(check_xml_cdata_for_version("hello world", XmlVersion::Xml11), None);
+}
+
+#[cfg(test)]
+#[test]
+fn sanitize_reject_policy_fails_on_invalid_char() {
+	let result = sanitize_xml_cdata(b"a\x01b".as_bstr(), InvalidCharPolicy::Reject);
+	assert_eq!(result, Err(TextConstraintError::InvalidCharacter(1, '\x01')));
+}
+
+#[cfg(test)]
+#[test]
+fn sanitize_drop_policy_strips_invalid_chars() {
+	let result = sanitize_xml_cdata(b"a\x01b".as_bstr(), InvalidCharPolicy::Drop).unwrap();
+	assert_eq!(result.text, "ab");
+	assert_eq!(result.altered, vec![1]);
+}
+
+#[cfg(test)]
+#[test]
+fn sanitize_replace_policy_substitutes_invalid_chars() {
+	let result = sanitize_xml_cdata(b"a\x01b".as_bstr(), InvalidCharPolicy::ReplaceWith('\u{fffd}')).unwrap();
+	assert_eq!(result.text, "a\u{fffd}b");
+	assert_eq!(result.altered, vec![1]);
+}
+
+#[cfg(test)]
+#[test]
+fn sanitize_valid_input_is_unchanged_and_reports_no_alterations() {
+	let result = sanitize_xml_cdata(b"hello".as_bstr(), InvalidCharPolicy::Drop).unwrap();
+	assert_eq!(result.text, "hello");
+	assert!(result.altered.is_empty());
+}
+
+/// Differential/property-based coverage for [`convert_xml_element_name`],
+/// [`convert_xml_attribute_name`], and [`convert_xml_cdata`].
+///
+/// There is no `cargo-fuzz`/`proptest` target here: a fuzz target is
+/// its own crate, and `proptest` is not among this crate's
+/// dependencies. Instead this reimplements the same two ideas by hand
+/// with only `std`: a small seeded PRNG standing in for `cargo-fuzz`'s
+/// arbitrary-byte-slice corpus, and an independently-written reference
+/// classifier (a literal `match` over the XML 1.0 productions, rather
+/// than a binary search over `VALID_XML_*_RANGES`) standing in for
+/// `proptest`'s "agrees with a reference implementation" check. A
+/// separate `cargo-fuzz` crate can reuse `reference_is_*` below as its
+/// oracle.
+#[cfg(test)]
+mod differential_tests {
+	use super::*;
+
+	/// A tiny deterministic xorshift64 PRNG -- not cryptographic, just
+	/// enough spread to exercise a wide variety of byte sequences
+	/// reproducibly without pulling in a `rand`/`proptest` dependency.
+	struct Xorshift64(u64);
+
+	impl Xorshift64 {
+		fn new(seed: u64) -> Self {
+			Xorshift64(seed | 1)
+		}
+
+		fn next_u64(&mut self) -> u64 {
+			let mut x = self.0;
+			x ^= x << 13;
+			x ^= x >> 7;
+			x ^= x << 17;
+			self.0 = x;
+			x
+		}
+
+		fn next_bytes(&mut self, len: usize) -> Vec<u8> {
+			let mut out = Vec::with_capacity(len);
+			while out.len() < len {
+				out.extend_from_slice(&self.next_u64().to_le_bytes());
+			}
+			out.truncate(len);
+			out
+		}
+	}
+
+	/// An independently-written reference classifier for XML 1.0 § 2.3
+	/// `NameStartChar`, coded as a literal `match` rather than a binary
+	/// search, so a bug in `in_ranges` or a transcription slip in
+	/// `VALID_XML_NAME_START_RANGES` doesn't silently pass by checking
+	/// itself.
+	fn reference_is_valid_name_start_char(c: char) -> bool {
+		match c {
+			':' | 'A'..='Z' | '_' | 'a'..='z' => true,
+			'\u{c0}'..='\u{d6}' => true,
+			'\u{d8}'..='\u{f6}' => true,
+			'\u{f8}'..='\u{2ff}' => true,
+			'\u{370}'..='\u{37d}' => true,
+			'\u{37f}'..='\u{1fff}' => true,
+			'\u{200c}'..='\u{200d}' => true,
+			'\u{2070}'..='\u{218f}' => true,
+			'\u{2c00}'..='\u{2fef}' => true,
+			'\u{3001}'..='\u{d7ff}' => true,
+			'\u{f900}'..='\u{fdcf}' => true,
+			'\u{10000}'..='\u{effff}' => true,
+			_ => false,
+		}
+	}
+
+	/// Reference classifier for XML 1.0 § 2.3 [4a] `NameChar`.
+	fn reference_is_valid_name_char(c: char) -> bool {
+		if reference_is_valid_name_start_char(c) {
+			return true;
+		}
+		match c {
+			'-' | '.' | '0'..='9' => true,
+			'\u{b7}' => true,
+			'\u{300}'..='\u{36f}' => true,
+			'\u{203f}'..='\u{2040}' => true,
+			_ => false,
+		}
+	}
+
+	/// Reference classifier for XML 1.0 § 2.2 `Char`.
+	fn reference_is_valid_cdata_char(c: char) -> bool {
+		match c {
+			'\x09' | '\x0a' | '\x0d' => true,
+			'\u{0020}'..='\u{d7ff}' => true,
+			'\u{e000}'..='\u{fffd}' => true,
+			'\u{10000}'..='\u{10ffff}' => true,
+			_ => false,
+		}
+	}
+
+	const SAMPLE_COUNT: u64 = 20_000;
+
+	#[test]
+	fn name_start_char_classification_matches_reference_over_bmp_and_beyond() {
+		let mut rng = Xorshift64::new(0x5eed_c0de_u64);
+		for _ in 0..SAMPLE_COUNT {
+			if let Some(c) = char::from_u32((rng.next_u64() % 0x11_0000) as u32) {
+				assert_eq!(
+					check_valid_name_start_char(c),
+					reference_is_valid_name_start_char(c),
+					"mismatch for {:?} ({:#x})", c, c as u32,
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn name_char_classification_matches_reference_over_bmp_and_beyond() {
+		let mut rng = Xorshift64::new(0xfeed_face_u64);
+		for _ in 0..SAMPLE_COUNT {
+			if let Some(c) = char::from_u32((rng.next_u64() % 0x11_0000) as u32) {
+				assert_eq!(
+					check_valid_name_char(c),
+					reference_is_valid_name_char(c),
+					"mismatch for {:?} ({:#x})", c, c as u32,
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn cdata_char_classification_matches_reference_over_bmp_and_beyond() {
+		let mut rng = Xorshift64::new(0xc0ffee00_u64);
+		for _ in 0..SAMPLE_COUNT {
+			if let Some(c) = char::from_u32((rng.next_u64() % 0x11_0000) as u32) {
+				assert_eq!(
+					check_valid_cdata_char(c),
+					reference_is_valid_cdata_char(c),
+					"mismatch for {:?} ({:#x})", c, c as u32,
+				);
+			}
+		}
+	}
+
+	/// Any `Ok(s)` out of `convert_xml_element_name` must itself pass
+	/// `check_xml_element_name` -- i.e. validation and conversion can't
+	/// disagree about what's valid.
+	#[test]
+	fn fuzz_element_name_ok_results_round_trip() {
+		let mut rng = Xorshift64::new(0xd15ea5e_u64);
+		for _ in 0..SAMPLE_COUNT {
+			let len = (rng.next_u64() % 16) as usize;
+			let bytes = rng.next_bytes(len);
+			if let Ok(s) = convert_xml_element_name(bytes.as_bstr()) {
+				assert_eq!(check_xml_element_name(s.as_str(), false), None);
+			}
+		}
+	}
+
+	/// Any `Ok(s)` out of `convert_xml_cdata` must itself pass
+	/// `check_xml_cdata`.
+	#[test]
+	fn fuzz_cdata_ok_results_round_trip() {
+		let mut rng = Xorshift64::new(0xba5eba11_u64);
+		for _ in 0..SAMPLE_COUNT {
+			let len = (rng.next_u64() % 16) as usize;
+			let bytes = rng.next_bytes(len);
+			if let Ok(s) = convert_xml_cdata(bytes.as_bstr()) {
+				assert_eq!(check_xml_cdata(s.as_str()), None);
+			}
+		}
+	}
+
+	/// Any `InvalidCharacter(pos, c)` returned must point at a *char*
+	/// index (not a byte index) whose decoded character really is the
+	/// one reported, and which really does fail the relevant check --
+	/// guards against an off-by-one in the `enumerate()` position
+	/// tracking shared by `check_xml_element_name`/`check_xml_cdata`.
+	#[test]
+	fn fuzz_invalid_character_error_position_is_a_char_index() {
+		let mut rng = Xorshift64::new(0x0ff1ce_u64);
+		for _ in 0..SAMPLE_COUNT {
+			let len = (rng.next_u64() % 16) as usize;
+			let bytes = rng.next_bytes(len);
+			if let Ok(s) = String::from_utf8(bytes) {
+				if let Some(TextConstraintError::InvalidCharacter(pos, c)) = check_xml_cdata(s.as_str()) {
+					let chars: Vec<char> = s.chars().collect();
+					assert_eq!(chars.get(pos), Some(&c));
+					assert!(!check_valid_cdata_char(c));
+				}
+				if let Some(TextConstraintError::InvalidCharacter(pos, c)) = check_xml_element_name(s.as_str(), false) {
+					let chars: Vec<char> = s.chars().collect();
+					assert_eq!(chars.get(pos), Some(&c));
+					if pos == 0 {
+						assert!(!check_valid_name_start_char(c));
+					} else {
+						assert!(!check_valid_name_char(c));
+					}
+				}
+			}
+		}
+	}
+}
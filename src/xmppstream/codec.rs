@@ -0,0 +1,143 @@
+//! `tokio_util::codec` adapter around [`XMPPStream`].
+//!
+//! Wraps the existing WouldBlock-based `feed`/`read` pull loop as a
+//! [`Decoder`] whose `Item` is [`Event`], and provides a matching
+//! [`Encoder`] for writing stream headers, stanzas, and stream
+//! footers back out. Gated behind the `tokio-codec` feature so that
+//! consumers who only need the parser don't have to pull in tokio.
+use std::fmt::Write as _;
+use std::io;
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::stanza;
+use super::stream::{Error, Event, StreamConfig, XMPPStream};
+
+/// Attributes for an outbound stream header / `<open/>` nonza.
+///
+/// Mirrors the fields carried by [`Event::Opened`].
+#[derive(Clone, Debug, Default)]
+pub struct Open {
+	pub id: Option<String>,
+	pub lang: Option<String>,
+	pub from: Option<String>,
+	pub to: Option<String>,
+	pub version: Option<String>,
+}
+
+/// Things which can be written onto the wire through an
+/// [`XMPPStreamCodec`].
+pub enum Frame {
+	/// The stream header, or, in framed mode, the `<open/>` nonza.
+	Open(Open),
+	/// A single stanza.
+	Stanza(stanza::Stanza),
+	/// The stream footer, or, in framed mode, the `<close/>` nonza.
+	Close,
+}
+
+/// A [`tokio_util::codec`] adapter around [`XMPPStream`].
+///
+/// `decode` drives the wrapped stream's `feed`/`read` loop, mapping
+/// `WouldBlock` onto `Ok(None)` so it can plug into a `Framed`
+/// transport. `encode` renders [`Frame`]s honoring the `StreamConfig`
+/// the codec was constructed with.
+pub struct XMPPStreamCodec<'x> {
+	stream: XMPPStream<'x>,
+}
+
+impl<'x> XMPPStreamCodec<'x> {
+	pub fn new(cfg: StreamConfig) -> XMPPStreamCodec<'x> {
+		XMPPStreamCodec{ stream: XMPPStream::new(cfg) }
+	}
+
+	pub fn cfg(&self) -> &StreamConfig {
+		self.stream.cfg()
+	}
+}
+
+impl<'x> Decoder for XMPPStreamCodec<'x> {
+	type Item = Event;
+	type Error = Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Event>, Error> {
+		if !src.is_empty() {
+			self.stream.feed(src.split().to_vec())?;
+		}
+		match self.stream.read() {
+			Ok(ev) => Ok(ev),
+			Err(Error::ParserError(rxml::Error::IO(ioerr))) if ioerr.kind() == io::ErrorKind::WouldBlock => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+// rendering never actually fails (we are writing into a `String`),
+// but `fmt::Write` forces us to deal with a `Result` anyway.
+fn write_attr(dst: &mut String, name: &str, value: &str) {
+	write!(dst, " {}='", name).unwrap();
+	stanza::xml::escape(dst, value).unwrap();
+	dst.push('\'');
+}
+
+impl<'x> XMPPStreamCodec<'x> {
+	fn render_open(&self, open: &Open) -> String {
+		let cfg = self.stream.cfg();
+		let mut out = String::new();
+		if cfg.framed {
+			out.push_str("<open xmlns='urn:ietf:params:xml:ns:xmpp-framing'");
+		} else {
+			out.push_str("<?xml version='1.0'?><stream:stream");
+			write_attr(&mut out, "xmlns:stream", cfg.stream_namespace.as_str());
+			write_attr(&mut out, "xmlns", cfg.default_namespace.as_str());
+		}
+		if let Some(id) = open.id.as_ref() {
+			write_attr(&mut out, "id", id);
+		}
+		if let Some(from) = open.from.as_ref() {
+			write_attr(&mut out, "from", from);
+		}
+		if let Some(to) = open.to.as_ref() {
+			write_attr(&mut out, "to", to);
+		}
+		if let Some(lang) = open.lang.as_ref() {
+			write_attr(&mut out, "xml:lang", lang);
+		}
+		if let Some(version) = open.version.as_ref() {
+			write_attr(&mut out, "version", version);
+		}
+		out.push_str(if cfg.framed { "/>" } else { ">" });
+		out
+	}
+
+	fn render_close(&self) -> &'static str {
+		if self.stream.cfg().framed {
+			"<close xmlns='urn:ietf:params:xml:ns:xmpp-framing'/>"
+		} else {
+			"</stream:stream>"
+		}
+	}
+}
+
+impl<'x> Encoder<Frame> for XMPPStreamCodec<'x> {
+	type Error = Error;
+
+	fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Error> {
+		match item {
+			Frame::Open(open) => {
+				dst.extend_from_slice(self.render_open(&open).as_bytes());
+			},
+			Frame::Stanza(st) => {
+				let formatter = stanza::xml::Formatter{ indent: None, initial_level: 0, max_width: None };
+				// building into a `String` cannot actually fail here.
+				let rendered = formatter.format(st.root()).unwrap();
+				dst.extend_from_slice(rendered.as_bytes());
+			},
+			Frame::Close => {
+				dst.extend_from_slice(self.render_close().as_bytes());
+			},
+		}
+		Ok(())
+	}
+}
@@ -33,6 +33,9 @@ fn capture_stream_config<'l>(tbl: &LuaTable<'l>) -> LuaResult<stream::StreamConf
 		default_namespace: ctx.intern_cdata(default_ns),
 		stream_namespace: ctx.intern_cdata(stream_ns),
 		init_ctx: Some(ctx),
+		framed: tbl.get::<_, Option<bool>>("framed")?.unwrap_or(false),
+		raw_passthrough: tbl.get::<_, Option<bool>>("raw_passthrough")?.unwrap_or(false),
+		emit_keepalives: tbl.get::<_, Option<bool>>("emit_keepalives")?.unwrap_or(false),
 	})
 }
 
@@ -116,7 +119,7 @@ impl<'x> ProsodyXmppStream<'x> {
 
 impl<'l> LuaUserData for ProsodyXmppStream<'l> {
 	fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
-		methods.add_function("feed", |l: &'lua Lua, (this, data): (LuaAnyUserData, BString)| -> LuaResult<stream::Result<bool>> {
+		methods.add_function("feed", |l: &'lua Lua, (this, data): (LuaAnyUserData<'lua>, BString)| -> LuaResult<stream::Result<bool>> {
 			{
 				let mut stream = this.borrow_mut::<ProsodyXmppStream>()?;
 				match stream.stream.feed(data.to_vec()) {
@@ -178,6 +181,9 @@ impl<'l> LuaUserData for ProsodyXmppStream<'l> {
 						drop(stream);
 						cb_error_or_ret(&cbtbl, &session, "stream-error", Some(st), None)?;
 					},
+					stream::Event::Keepalive => {
+						drop(stream);
+					},
 				}
 			}
 		});
@@ -0,0 +1,7 @@
+pub(crate) mod stream;
+pub(crate) mod lua;
+pub(crate) mod rawsplit;
+#[cfg(feature = "tokio-codec")]
+pub(crate) mod codec;
+
+pub use stream::{XMPPStream, StreamConfig, Event, Error, Result};
@@ -0,0 +1,309 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+	/// between tags
+	Outside,
+	/// just consumed `<`, waiting to see what kind of construct follows
+	AfterLt,
+	/// consumed `<!`, buffering bytes to tell a comment (`<!--`) from a
+	/// CDATA section (`<![CDATA[`) apart; anything else (e.g. a stray
+	/// `<!DOCTYPE`) falls back to being treated as a regular tag
+	Bang(Vec<u8>),
+	/// inside a `<?...?>` processing instruction; the bool tracks
+	/// whether the previous byte was `?`
+	Pi(bool),
+	/// inside a `<!--...-->` comment; the count saturates at 2 and
+	/// tracks consecutive trailing `-`s
+	Comment(u8),
+	/// inside a `<![CDATA[...]]>` section; the count saturates at 2
+	/// and tracks consecutive trailing `]`s
+	CData(u8),
+	/// inside a start or end tag, up to its closing `>`
+	Tag{ closing: bool, quote: Option<u8>, prev_slash: bool },
+}
+
+/// Minimal, non-validating byte scanner that tracks XML element
+/// nesting depth just well enough to recover the exact byte range of
+/// each top-level construct seen at the level it watches, without
+/// building a tree or otherwise validating the XML.
+///
+/// This lets [`XMPPStream`](super::stream::XMPPStream) hand back a
+/// stanza's *original* bytes alongside the parsed
+/// [`stanza::Stanza`](crate::stanza::Stanza), so a proxy can forward
+/// it verbatim instead of reserializing a parsed tree (which would
+/// normalize attribute order, whitespace, and namespace prefix
+/// choices).
+///
+/// `depth` counts nesting from the very first byte ever fed.
+/// `watch_depth` is the depth at which top-level constructs are
+/// reported: `0` for e.g. XMPP-over-WebSocket framed mode, where each
+/// fed message is its own independent document, or `1` for a classic
+/// `<stream:stream>`-wrapped session, where stanzas are nested one
+/// level below the ever-open stream root (which itself is never
+/// reported). Anything that opens and closes while depth stays at
+/// `watch_depth` the whole time -- a self-closing tag, a matched tag
+/// pair, or a standalone PI/comment/CDATA section -- is reported via
+/// the `on_span` callback passed to [`RawSplitter::feed`].
+pub struct RawSplitter {
+	state: State,
+	depth: usize,
+	watch_depth: usize,
+	span_start: Option<usize>,
+	offset: usize,
+}
+
+impl RawSplitter {
+	pub fn new(watch_depth: usize) -> RawSplitter {
+		RawSplitter{
+			state: State::Outside,
+			depth: 0,
+			watch_depth: watch_depth,
+			span_start: None,
+			offset: 0,
+		}
+	}
+
+	/// The nesting depth right now; `watch_depth` means the next
+	/// completed construct at that level will be reported.
+	pub fn depth(&self) -> usize {
+		self.depth
+	}
+
+	/// Feed another chunk of bytes, contiguous with everything fed to
+	/// this splitter so far, invoking `on_span` with the `[start,
+	/// end)` byte range (relative to the very first byte ever fed) of
+	/// each top-level construct completed while processing it.
+	pub fn feed(&mut self, data: &[u8], mut on_span: impl FnMut(Range<usize>)) {
+		for (i, &byte) in data.iter().enumerate() {
+			self.step(byte, self.offset + i, &mut on_span);
+		}
+		self.offset += data.len();
+	}
+
+	fn leave(&mut self, pos: usize, on_span: &mut impl FnMut(Range<usize>)) {
+		self.depth = self.depth.saturating_sub(1);
+		if self.depth == self.watch_depth {
+			if let Some(start) = self.span_start.take() {
+				on_span(start..pos + 1);
+			}
+		}
+	}
+
+	fn complete_standalone(&mut self, pos: usize, on_span: &mut impl FnMut(Range<usize>)) {
+		if self.depth == self.watch_depth {
+			if let Some(start) = self.span_start.take() {
+				on_span(start..pos + 1);
+			}
+		}
+	}
+
+	// process one byte while inside a start/end tag (after the
+	// disambiguating byte that got us there); returns the state to
+	// continue with.
+	fn tag_byte(&mut self, closing: bool, mut quote: Option<u8>, mut prev_slash: bool, byte: u8, pos: usize, on_span: &mut impl FnMut(Range<usize>)) -> State {
+		if let Some(q) = quote {
+			if byte == q {
+				quote = None;
+			}
+			return State::Tag{ closing, quote, prev_slash: false };
+		}
+		match byte {
+			b'\'' | b'"' => State::Tag{ closing, quote: Some(byte), prev_slash: false },
+			b'/' => State::Tag{ closing, quote, prev_slash: true },
+			b'>' => {
+				if closing {
+					self.leave(pos, on_span);
+				} else if prev_slash {
+					self.complete_standalone(pos, on_span);
+				} else {
+					self.depth += 1;
+				}
+				State::Outside
+			},
+			_ => State::Tag{ closing, quote, prev_slash: false },
+		}
+	}
+
+	fn step(&mut self, byte: u8, pos: usize, on_span: &mut impl FnMut(Range<usize>)) {
+		let state = std::mem::replace(&mut self.state, State::Outside);
+		self.state = match state {
+			State::Outside => {
+				if byte == b'<' {
+					if self.depth == self.watch_depth {
+						self.span_start = Some(pos);
+					}
+					State::AfterLt
+				} else {
+					State::Outside
+				}
+			},
+			State::AfterLt => match byte {
+				b'?' => State::Pi(false),
+				b'/' => State::Tag{ closing: true, quote: None, prev_slash: false },
+				b'!' => State::Bang(Vec::new()),
+				_ => self.tag_byte(false, None, false, byte, pos, on_span),
+			},
+			State::Bang(mut buf) => {
+				buf.push(byte);
+				if buf == b"--" {
+					State::Comment(0)
+				} else if buf.len() <= 2 && buf.as_slice() == &b"--"[..buf.len()] {
+					// still just a prefix of "--"; need one more byte
+					// before we can tell a comment from anything else
+					State::Bang(buf)
+				} else if buf.len() <= 7 && buf.as_slice() == &b"[CDATA["[..buf.len()] {
+					if buf.len() == 7 {
+						State::CData(0)
+					} else {
+						State::Bang(buf)
+					}
+				} else {
+					// not a comment or CDATA section after all (e.g.
+					// `<!DOCTYPE`); fall back to tracking it like a
+					// regular tag so depth bookkeeping stays correct.
+					self.tag_byte(false, None, false, byte, pos, on_span)
+				}
+			},
+			State::Pi(prev_question) => {
+				if byte == b'>' && prev_question {
+					self.complete_standalone(pos, on_span);
+					State::Outside
+				} else {
+					State::Pi(byte == b'?')
+				}
+			},
+			State::Comment(dashes) => {
+				if byte == b'>' && dashes >= 2 {
+					self.complete_standalone(pos, on_span);
+					State::Outside
+				} else if byte == b'-' {
+					State::Comment((dashes + 1).min(2))
+				} else {
+					State::Comment(0)
+				}
+			},
+			State::CData(brackets) => {
+				if byte == b'>' && brackets >= 2 {
+					self.complete_standalone(pos, on_span);
+					State::Outside
+				} else if byte == b']' {
+					State::CData((brackets + 1).min(2))
+				} else {
+					State::CData(0)
+				}
+			},
+			State::Tag{ closing, quote, prev_slash } => self.tag_byte(closing, quote, prev_slash, byte, pos, on_span),
+		};
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn spans(data: &[u8]) -> Vec<Range<usize>> {
+		let mut splitter = RawSplitter::new(0);
+		let mut out = Vec::new();
+		splitter.feed(data, |r| out.push(r));
+		out
+	}
+
+	#[test]
+	fn finds_self_closing_top_level_tag() {
+		let data = b"<iq/>";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn finds_tag_pair_with_child() {
+		let data = b"<message><body>hi</body></message>";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn finds_multiple_top_level_siblings() {
+		let data = b"<iq id='1'/><iq id='2'/>";
+		assert_eq!(spans(data), vec![0..12, 12..24]);
+	}
+
+	#[test]
+	fn ignores_gt_inside_attribute_value() {
+		let data = b"<iq note='a > b'/>";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn ignores_angle_brackets_inside_comment() {
+		let data = b"<iq><!-- <weird> --></iq>";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn ignores_angle_brackets_inside_cdata() {
+		let data = b"<body><![CDATA[1 < 2 > 0]]></body>";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn reports_standalone_comment_at_stream_level() {
+		let data = b"<!-- hi -->";
+		assert_eq!(spans(data), vec![0..data.len()]);
+	}
+
+	#[test]
+	fn inner_constructs_do_not_affect_depth() {
+		let data = b"<message><!-- note --><body><![CDATA[x]]></body></message><iq/>";
+		let first_end = b"<message><!-- note --><body><![CDATA[x]]></body></message>".len();
+		assert_eq!(spans(data), vec![0..first_end, first_end..data.len()]);
+	}
+
+	#[test]
+	fn splits_work_across_feed_calls() {
+		let data = b"<message><body>hi</body></message>";
+		for split_at in 1..data.len() {
+			let mut splitter = RawSplitter::new(0);
+			let mut out = Vec::new();
+			splitter.feed(&data[..split_at], |r| out.push(r));
+			splitter.feed(&data[split_at..], |r| out.push(r));
+			assert_eq!(out, vec![0..data.len()], "split at {}", split_at);
+		}
+	}
+
+	#[test]
+	fn depth_tracks_nesting() {
+		let mut splitter = RawSplitter::new(0);
+		splitter.feed(b"<message>", |_| panic!("no span expected yet"));
+		assert_eq!(splitter.depth(), 1);
+		splitter.feed(b"<body>", |_| panic!("no span expected yet"));
+		assert_eq!(splitter.depth(), 2);
+		splitter.feed(b"</body>", |_| panic!("no span expected yet"));
+		assert_eq!(splitter.depth(), 1);
+		let mut out = Vec::new();
+		splitter.feed(b"</message>", |r| out.push(r));
+		assert_eq!(splitter.depth(), 0);
+		assert_eq!(out, vec![0.."<message><body></body></message>".len()]);
+	}
+
+	#[test]
+	fn watch_depth_one_skips_the_stream_root_and_finds_children() {
+		let header = b"<stream:stream xmlns:stream='streamns' xmlns='stanzans'>";
+		let iq = b"<iq id='1'/>";
+		let message = b"<message><body>hi</body></message>";
+		let footer = b"</stream:stream>";
+		let mut data = Vec::new();
+		data.extend_from_slice(header);
+		data.extend_from_slice(iq);
+		data.extend_from_slice(message);
+		data.extend_from_slice(footer);
+
+		let mut splitter = RawSplitter::new(1);
+		let mut out = Vec::new();
+		splitter.feed(&data, |r| out.push(r));
+
+		let iq_start = header.len();
+		let iq_end = iq_start + iq.len();
+		let message_end = iq_end + message.len();
+		assert_eq!(out, vec![iq_start..iq_end, iq_end..message_end]);
+	}
+}
@@ -1,16 +1,22 @@
 use std::fmt;
 use std::error;
 use std::io;
+use std::io::BufRead;
 use std::rc::Rc;
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 use std::result::Result as StdResult;
 use std::borrow::Cow;
 use rxml::EventRead;
 
 use crate::stanza;
+use super::rawsplit::RawSplitter;
 
 pub const XMLNS_STREAMS: &'static str = "http://etherx.jabber.org/streams";
+/// Namespace of the `<open/>`/`<close/>` framing nonzas used by XMPP
+/// over WebSocket (RFC 7395), in lieu of a `<stream:stream>` header
+/// and footer.
+pub const XMLNS_FRAMING: &'static str = "urn:ietf:params:xml:ns:xmpp-framing";
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Error {
@@ -72,6 +78,9 @@ pub enum Event {
 	Error(stanza::Stanza),
 	/// stream footer
 	Closed,
+	/// whitespace-only text at stream level, sent by a peer as a
+	/// keepalive ping; only emitted when `cfg.emit_keepalives` is set
+	Keepalive,
 }
 
 enum ProcResult {
@@ -87,6 +96,24 @@ pub struct StreamConfig {
 	pub stream_localname: String,
 	pub error_localname: String,
 	pub init_ctx: Option<Rc<rxml::Context>>,
+	/// When set, `XMPPStream` expects XMPP-over-WebSocket framing
+	/// (RFC 7395) instead of a classic `<stream:stream>` document:
+	/// every fed message is its own complete XML document, opened and
+	/// closed by standalone `<open/>`/`<close/>` nonzas rather than a
+	/// single long-lived stream header/footer.
+	pub framed: bool,
+	/// When set, `XMPPStream` additionally tracks the exact original
+	/// bytes of each top-level stanza as it is fed, made available via
+	/// [`XMPPStream::take_raw_stanza`] right after the matching
+	/// `Event::Stanza`/`Event::Error`, so a proxy can forward it
+	/// verbatim instead of reserializing the parsed tree.
+	pub raw_passthrough: bool,
+	/// When set, whitespace-only text at stream level is surfaced as
+	/// [`Event::Keepalive`] instead of being silently discarded, so a
+	/// consumer can reset its own read timeout (and optionally echo the
+	/// keepalive back). Non-whitespace text at stream level is always
+	/// rejected with [`Error::TextAtStreamLevel`], regardless of this flag.
+	pub emit_keepalives: bool,
 }
 
 impl fmt::Debug for StreamConfig {
@@ -96,6 +123,9 @@ impl fmt::Debug for StreamConfig {
 			.field("default_namespace", &(&*self.default_namespace as *const rxml::CData, &*self.default_namespace))
 			.field("stream_localname", &self.stream_localname)
 			.field("error_localname", &self.error_localname)
+			.field("framed", &self.framed)
+			.field("raw_passthrough", &self.raw_passthrough)
+			.field("emit_keepalives", &self.emit_keepalives)
 			.finish()
 	}
 }
@@ -108,6 +138,9 @@ impl StreamConfig {
 			stream_localname: "stream".to_string(),
 			error_localname: "error".to_string(),
 			init_ctx: None,
+			framed: false,
+			raw_passthrough: false,
+			emit_keepalives: false,
 		}
 	}
 
@@ -118,12 +151,31 @@ impl StreamConfig {
 			stream_localname: "stream".to_string(),
 			error_localname: "error".to_string(),
 			init_ctx: None,
+			framed: false,
+			raw_passthrough: false,
+			emit_keepalives: false,
 		}
 	}
+
+	/// Like [`StreamConfig::c2s`], but for an XMPP-over-WebSocket
+	/// client-to-server connection framed per RFC 7395.
+	pub fn c2s_framed() -> StreamConfig {
+		StreamConfig{ framed: true, ..StreamConfig::c2s() }
+	}
+
+	/// Like [`StreamConfig::s2s`], but for an XMPP-over-WebSocket
+	/// server-to-server connection framed per RFC 7395.
+	pub fn s2s_framed() -> StreamConfig {
+		StreamConfig{ framed: true, ..StreamConfig::s2s() }
+	}
 }
 
 pub struct XMPPStream<'x> {
 	p: rxml::FeedParser<'x>,
+	// kept around (in addition to being handed to `p`) so that, in
+	// framed mode, we can start a fresh `FeedParser` for the next frame
+	// without losing interned namespaces/strings.
+	ctx: Rc<rxml::Context>,
 	is_open: bool,
 	stanza: Option<stanza::Stanza>,
 	stanza_size: usize,
@@ -132,6 +184,18 @@ pub struct XMPPStream<'x> {
 	non_streamns_depth: usize,
 	cfg: StreamConfig,
 	err: Option<Error>,
+	// only present when `cfg.raw_passthrough` is set; watches depth 0
+	// in framed mode (every frame is its own top-level construct) or
+	// depth 1 in classic mode (stanzas nested below the stream root).
+	splitter: Option<RawSplitter>,
+	// bytes fed since `raw_base`; trimmed from the front every time a
+	// span is claimed by `take_raw_stanza`.
+	raw_buf: Vec<u8>,
+	// absolute offset (in the never-truncated byte stream seen by
+	// `splitter`) corresponding to `raw_buf[0]`.
+	raw_base: usize,
+	raw_spans: VecDeque<Range<usize>>,
+	last_raw_stanza: Option<Vec<u8>>,
 }
 
 impl fmt::Debug for XMPPStream<'_> {
@@ -160,7 +224,7 @@ fn convert_attrs(mut rxmlattrs: HashMap<rxml::QName, rxml::CData>) -> HashMap<st
 			Some(nsuri) => stanza::AttrName::compose(Some(&*nsuri), localname),
 			None => stanza::AttrName::local_only(localname),
 		};
-		out.insert(key, value.as_string());
+		out.insert(key, value.as_str().to_string());
 	}
 	out
 }
@@ -185,17 +249,66 @@ fn accum_bytes(accum: &mut usize, add: usize) -> Result<()> {
 	Ok(())
 }
 
+struct OpenAttrs {
+	id: Option<rxml::CData>,
+	lang: Option<rxml::CData>,
+	from: Option<rxml::CData>,
+	to: Option<rxml::CData>,
+	version: Option<rxml::CData>,
+}
+
+// shared between the classic `<stream:stream ...>` header and the
+// framed `<open .../>` nonza, which carry the same attributes.
+fn parse_open_attrs(mut attrs: HashMap<rxml::QName, rxml::CData>) -> Result<OpenAttrs> {
+	let mut id: Option<rxml::CData> = None;
+	let mut lang: Option<rxml::CData> = None;
+	let mut from: Option<rxml::CData> = None;
+	let mut to: Option<rxml::CData> = None;
+	let mut version: Option<rxml::CData> = None;
+	for ((nsuri, localname), value) in attrs.drain() {
+		match (nsuri.as_ref().and_then(|x| { Some(x.as_str()) }).unwrap_or(""), localname.as_str()) {
+			("", "id") => {
+				id = Some(value);
+			},
+			(ns, "lang") if ns == rxml::XMLNS_XML => {
+				lang = Some(value);
+			},
+			("", "from") => {
+				from = Some(value);
+			},
+			("", "to") => {
+				to = Some(value);
+			},
+			("", "version") => {
+				version = Some(value);
+			},
+			_ => {
+				return Err(Error::InvalidStreamHeader)
+			}
+		}
+	}
+	Ok(OpenAttrs{id, lang, from, to, version})
+}
+
+fn is_framing_element(nsuri: &Option<Rc<rxml::CData>>, localname: &str, want: &str) -> bool {
+	nsuri.as_ref().map(|n| n.as_str() == XMLNS_FRAMING).unwrap_or(false) && localname == want
+}
+
 impl<'x> XMPPStream<'x> {
 	// TODO: options!
 	pub fn new<'a>(mut cfg: StreamConfig) -> XMPPStream<'a> {
 		let mut ctx: Option<Rc<rxml::Context>> = None;
 		// no need to preserve a reference to the context in the cfg, too
 		std::mem::swap(&mut cfg.init_ctx, &mut ctx);
+		let ctx = ctx.unwrap_or_else(|| Rc::new(rxml::Context::new()));
+		let splitter = if cfg.raw_passthrough {
+			Some(RawSplitter::new(if cfg.framed { 0 } else { 1 }))
+		} else {
+			None
+		};
 		XMPPStream{
-			p: match ctx {
-				Some(ctx) => rxml::FeedParser::with_context(ctx),
-				None => rxml::FeedParser::new(),
-			},
+			p: rxml::FeedParser::with_context(ctx.clone()),
+			ctx: ctx,
 			is_open: false,
 			stanza: None,
 			stanza_size: 0,
@@ -204,9 +317,27 @@ impl<'x> XMPPStream<'x> {
 			non_streamns_depth: 0,
 			cfg: cfg,
 			err: None,
+			splitter: splitter,
+			raw_buf: Vec::new(),
+			raw_base: 0,
+			raw_spans: VecDeque::new(),
+			last_raw_stanza: None,
 		}
 	}
 
+	// pop the next completed top-level span off `raw_spans`, slice it
+	// out of `raw_buf` and advance `raw_base` past it, dropping
+	// whatever (if anything) preceded it in the buffer along the way.
+	fn take_raw_frame(&mut self) -> Option<Vec<u8>> {
+		let span = self.raw_spans.pop_front()?;
+		let local_end = span.end - self.raw_base;
+		let local_start = span.start - self.raw_base;
+		let bytes = self.raw_buf[local_start..local_end].to_vec();
+		self.raw_buf.drain(..local_end);
+		self.raw_base = span.end;
+		Some(bytes)
+	}
+
 	fn check_poison(&self) -> Result<()> {
 		match self.err.as_ref() {
 			Some(e) => Err(e.clone()),
@@ -221,6 +352,60 @@ impl<'x> XMPPStream<'x> {
 		e
 	}
 
+	// in framed mode, every frame is an independent XML document, so
+	// the parser's document-level state (in particular, "have I
+	// already seen a root element") has to be reset between frames;
+	// we keep reusing `self.ctx` so namespace/string interning still
+	// pays off across frames.
+	fn reset_frame(&mut self) {
+		let mut leftover = Vec::new();
+		loop {
+			let chunk = match self.p.get_buffer_mut().fill_buf() {
+				Ok(chunk) if !chunk.is_empty() => chunk.to_vec(),
+				_ => break,
+			};
+			leftover.extend_from_slice(&chunk);
+			self.p.get_buffer_mut().consume(chunk.len());
+		}
+		let mut fresh = rxml::FeedParser::with_context(self.ctx.clone());
+		if !leftover.is_empty() {
+			fresh.feed(leftover);
+		}
+		self.p = fresh;
+	}
+
+	/// Discard the current XML stream and start over, as required after
+	/// STARTTLS and after SASL authentication: both negotiations end
+	/// with the two peers agreeing, out of band, to throw away
+	/// everything parsed so far and for the client to send a fresh
+	/// `<stream:stream>` header over the same underlying transport.
+	///
+	/// Clears `is_open`, drops any in-progress `stanza`, zeroes
+	/// `stanza_size`/`non_streamns_depth`/`pending_bytes`, clears a
+	/// poisoned `err`, and reinitializes the underlying
+	/// `rxml::FeedParser`, reusing
+	/// `self.ctx` so interned namespaces/strings survive the reset.
+	/// The next `read()` again expects and emits an `Event::Opened`.
+	pub fn reset(&mut self) {
+		self.is_open = false;
+		self.stanza = None;
+		self.stanza_size = 0;
+		self.non_streamns_depth = 0;
+		self.pending_bytes = 0;
+		self.err = None;
+		self.p = rxml::FeedParser::with_context(self.ctx.clone());
+		// the old stream's bytes are gone along with the parser that
+		// was tracking them, so any raw-passthrough bookkeeping for
+		// them is meaningless now too.
+		if self.splitter.is_some() {
+			self.splitter = Some(RawSplitter::new(if self.cfg.framed { 0 } else { 1 }));
+		}
+		self.raw_buf.clear();
+		self.raw_base = 0;
+		self.raw_spans.clear();
+		self.last_raw_stanza = None;
+	}
+
 	fn proc_event(&mut self, ev: rxml::Event) -> Result<ProcResult> {
 		match (ev, self.is_open, self.stanza.as_mut()) {
 			// we don’t do anything with the xml declaration
@@ -228,41 +413,28 @@ impl<'x> XMPPStream<'x> {
 				account_bytes(&mut self.pending_bytes, em.len());
 				Ok(ProcResult::NeedMore)
 			},
-			(rxml::Event::StartElement(em, (nsuri, localname), mut attrs), false, _) => {
-				// stream header
-				if nsuri.is_none() || nsuri.unwrap().as_str() != *self.cfg.stream_namespace || localname != self.cfg.stream_localname.as_str() {
-					return Err(Error::InvalidStreamHeader);
-				}
-				let mut id: Option<rxml::CData> = None;
-				let mut lang: Option<rxml::CData> = None;
-				let mut from: Option<rxml::CData> = None;
-				let mut to: Option<rxml::CData> = None;
-				let mut version: Option<rxml::CData> = None;
-				for ((nsuri, localname), value) in attrs.drain() {
-					match (nsuri.as_ref().and_then(|x| { Some(x.as_str()) }).unwrap_or(""), localname.as_str()) {
-						("", "id") => {
-							id = Some(value);
-						},
-						(ns, "lang") if ns == rxml::XMLNS_XML => {
-							lang = Some(value);
-						},
-						("", "from") => {
-							from = Some(value);
-						},
-						("", "to") => {
-							to = Some(value);
-						},
-						("", "version") => {
-							version = Some(value);
-						},
-						_ => {
-							return Err(Error::InvalidStreamHeader)
-						}
+			(rxml::Event::StartElement(em, (nsuri, localname), attrs), false, _) => {
+				// stream header, or, in framed mode, the `<open/>` nonza
+				if self.cfg.framed {
+					if !is_framing_element(&nsuri, localname.as_str(), "open") {
+						return Err(Error::InvalidStreamHeader);
 					}
+				} else if nsuri.is_none() || nsuri.unwrap().as_str() != *self.cfg.stream_namespace || localname != self.cfg.stream_localname.as_str() {
+					return Err(Error::InvalidStreamHeader);
 				}
+				let OpenAttrs{id, lang, from, to, version} = parse_open_attrs(attrs)?;
 				self.is_open = true;
 				account_bytes(&mut self.pending_bytes, em.len());
 				self.p.release_temporaries();
+				if self.cfg.framed {
+					// in framed mode the `<open/>` nonza is itself a
+					// self-closing top-level construct, so the splitter
+					// already produced a span for it; in classic
+					// `<stream:stream>` mode the header tag never closes,
+					// so there is no span to claim here -- the first one
+					// queued belongs to the first actual stanza.
+					self.last_raw_stanza = self.take_raw_frame();
+				}
 				Ok(ProcResult::Event(Event::Opened{
 					lang: lang,
 					from: from,
@@ -271,12 +443,18 @@ impl<'x> XMPPStream<'x> {
 					version: version,
 				}))
 			},
+			(rxml::Event::StartElement(em, (nsuri, localname), _attrs), true, None) if self.cfg.framed && is_framing_element(&nsuri, localname.as_str(), "close") => {
+				account_bytes(&mut self.pending_bytes, em.len());
+				self.p.release_temporaries();
+				self.last_raw_stanza = self.take_raw_frame();
+				Ok(ProcResult::Event(Event::Closed))
+			},
 			(rxml::Event::StartElement(em, (nsuri, localname), attrs), true, st_opt) => {
 				let mut converted_attrs = convert_attrs(attrs);
 				let nsuri = match nsuri {
 					None => None,
 					Some(nsuri) => {
-						if **nsuri != self.cfg.default_namespace.as_str() || self.non_streamns_depth > 0 {
+						if nsuri.as_str() != self.cfg.default_namespace.as_str() || self.non_streamns_depth > 0 {
 							self.non_streamns_depth = match self.non_streamns_depth.checked_add(1) {
 								None => return Err(Error::ParserError(rxml::Error::RestrictedXml("nested too deep"))),
 								Some(v) => v,
@@ -316,17 +494,30 @@ impl<'x> XMPPStream<'x> {
 				} else {
 					// make sure to not get excessive memory use from whitespace pings
 					self.p.release_temporaries();
-					Ok(ProcResult::NeedMore)
+					if self.cfg.emit_keepalives {
+						Ok(ProcResult::Event(Event::Keepalive))
+					} else {
+						Ok(ProcResult::NeedMore)
+					}
 				}
 			},
 			(rxml::Event::Text(em, cdata), _, Some(st)) => {
 				accum_bytes(&mut self.stanza_size, em.len())?;
-				st.text(cdata.as_string());
+				st.text(cdata.as_str().to_string());
 				Ok(ProcResult::NeedMore)
 			},
 			(rxml::Event::EndElement(em), _, None) => {
 				account_bytes(&mut self.pending_bytes, em.len());
-				Ok(ProcResult::Event(Event::Closed))
+				if self.cfg.framed {
+					// matching close tag of the self-closing `<open/>` or
+					// `<close/>` nonza; the corresponding event was
+					// already emitted when we saw its start tag, so this
+					// just ends the current frame.
+					self.reset_frame();
+					Ok(ProcResult::NeedMore)
+				} else {
+					Ok(ProcResult::Event(Event::Closed))
+				}
 			},
 			(rxml::Event::EndElement(em), _, Some(st)) => {
 				accum_bytes(&mut self.stanza_size, em.len())?;
@@ -347,6 +538,10 @@ impl<'x> XMPPStream<'x> {
 						ProcResult::Event(Event::Stanza(swap))
 					};
 					self.p.release_temporaries();
+					self.last_raw_stanza = self.take_raw_frame();
+					if self.cfg.framed {
+						self.reset_frame();
+					}
 					Ok(ev)
 				} else {
 					st.up();
@@ -370,6 +565,11 @@ impl<'x> XMPPStream<'x> {
 			Some(v) if v < self.pending_bytes => return Err(Error::StanzaLimitExceeded),
 			_ => (),
 		}
+		if let Some(splitter) = self.splitter.as_mut() {
+			self.raw_buf.extend_from_slice(&data);
+			let raw_spans = &mut self.raw_spans;
+			splitter.feed(&data, |span| raw_spans.push_back(span));
+		}
 		self.p.feed(data);
 		Ok(())
 	}
@@ -407,6 +607,18 @@ impl<'x> XMPPStream<'x> {
 		&self.cfg
 	}
 
+	/// The exact original bytes of the top-level construct (stanza,
+	/// stream error, or, in framed mode, `<open/>`/`<close/>` nonza)
+	/// that produced the event most recently returned by [`read`][Self::read],
+	/// if `cfg.raw_passthrough` is set.
+	///
+	/// Only meaningful right after `read` has returned `Event::Opened`,
+	/// `Event::Stanza`, `Event::Error`, or (in framed mode) `Event::Closed`;
+	/// calling it again before the next such event returns `None`.
+	pub fn take_raw_stanza(&mut self) -> Option<Vec<u8>> {
+		self.last_raw_stanza.take()
+	}
+
 	pub fn release_temporaries(&mut self) {
 		self.p.release_temporaries()
 	}
@@ -587,6 +799,37 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn xmppstream_emits_keepalive_for_stream_level_whitespace_when_opted_in() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'>    <iq/>"[..];
+		let mut s = XMPPStream::new(StreamConfig{ emit_keepalives: true, ..StreamConfig::c2s() });
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Keepalive => (),
+			other => panic!("unexpected event: {:?}", other),
+		}
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => assert_eq!(st.root().localname, "iq"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_still_rejects_non_whitespace_text_with_keepalives_enabled() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'>    foobar<iq/>"[..];
+		let mut s = XMPPStream::new(StreamConfig{ emit_keepalives: true, ..StreamConfig::c2s() });
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		let e = s.read().err().unwrap();
+		match e {
+			Error::TextAtStreamLevel => (),
+			other => panic!("unexpected error: {:?}", other),
+		}
+	}
+
 	#[test]
 	fn xmppstream_rejects_stream_level_text() {
 		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'>    foobar<iq/>"[..];
@@ -695,6 +938,9 @@ mod tests {
 			stream_localname: "stream".to_string(),
 			error_localname: "error".to_string(),
 			init_ctx: None,
+			framed: false,
+			raw_passthrough: false,
+			emit_keepalives: false,
 		});
 		s.feed(data).unwrap();
 		assert_eq!(s.pending_bytes, data.len());
@@ -727,4 +973,206 @@ mod tests {
 			other => panic!("unexpected event: {:?}", other),
 		}
 	}
+
+	#[test]
+	fn xmppstream_framed_reads_open_nonza() {
+		let data = &b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' to='example.com' version='1.0'/>"[..];
+		let mut s = XMPPStream::new(StreamConfig::c2s_framed());
+		s.feed(data).unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Opened{to, version, ..} => {
+				assert_eq!(to.unwrap(), "example.com");
+				assert_eq!(version.unwrap(), "1.0");
+			},
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_framed_reads_stanza_after_open_in_separate_frame() {
+		let mut s = XMPPStream::new(StreamConfig::c2s_framed());
+		s.feed(&b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' version='1.0'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+
+		s.feed(&b"<iq type='get' id='foo'/>"[..]).unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => {
+				let root = st.root();
+				assert_eq!(root.localname, "iq");
+				assert_eq!(root.attr.get("id").unwrap(), "foo");
+			},
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_framed_reads_multiple_stanza_frames() {
+		let mut s = XMPPStream::new(StreamConfig::c2s_framed());
+		s.feed(&b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' version='1.0'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+
+		s.feed(&b"<iq type='get' id='first'/>"[..]).unwrap();
+		match s.read().unwrap().unwrap() {
+			Event::Stanza(st) => assert_eq!(st.root().attr.get("id").unwrap(), "first"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+
+		s.feed(&b"<iq type='get' id='second'/>"[..]).unwrap();
+		match s.read().unwrap().unwrap() {
+			Event::Stanza(st) => assert_eq!(st.root().attr.get("id").unwrap(), "second"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_framed_reads_close_nonza() {
+		let mut s = XMPPStream::new(StreamConfig::c2s_framed());
+		s.feed(&b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' version='1.0'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+
+		s.feed(&b"<close xmlns='urn:ietf:params:xml:ns:xmpp-framing'/>"[..]).unwrap();
+		match s.read().unwrap().unwrap() {
+			Event::Closed => (),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_raw_passthrough_returns_none_when_disabled() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='foobar2342'/>"[..];
+		let mut s = XMPPStream::new(StreamConfig::c2s());
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(s.take_raw_stanza(), None);
+	}
+
+	#[test]
+	fn xmppstream_raw_passthrough_returns_original_stanza_bytes() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq  id='foobar2342'><query xmlns='urn:foo'/></iq>"[..];
+		let mut s = XMPPStream::new(StreamConfig{ raw_passthrough: true, ..StreamConfig::c2s() });
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(s.take_raw_stanza(), None);
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => assert_eq!(st.root().localname, "iq"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+		assert_eq!(
+			s.take_raw_stanza().unwrap(),
+			b"<iq  id='foobar2342'><query xmlns='urn:foo'/></iq>".to_vec(),
+		);
+	}
+
+	#[test]
+	fn xmppstream_raw_passthrough_returns_multiple_stanzas_in_order() {
+		let mut s = XMPPStream::new(StreamConfig{ raw_passthrough: true, ..StreamConfig::c2s() });
+		s.feed(&b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+
+		s.feed(&b"<iq id='first'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(s.take_raw_stanza().unwrap(), b"<iq id='first'/>".to_vec());
+
+		s.feed(&b"<iq id='second'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(s.take_raw_stanza().unwrap(), b"<iq id='second'/>".to_vec());
+	}
+
+	#[test]
+	fn xmppstream_raw_passthrough_works_for_framed_open_stanza_and_close() {
+		let cfg = StreamConfig{ raw_passthrough: true, ..StreamConfig::c2s_framed() };
+		let mut s = XMPPStream::new(cfg);
+
+		s.feed(&b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' version='1.0'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(
+			s.take_raw_stanza().unwrap(),
+			b"<open xmlns='urn:ietf:params:xml:ns:xmpp-framing' version='1.0'/>".to_vec(),
+		);
+
+		s.feed(&b"<iq id='foo'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(s.take_raw_stanza().unwrap(), b"<iq id='foo'/>".to_vec());
+
+		s.feed(&b"<close xmlns='urn:ietf:params:xml:ns:xmpp-framing'/>"[..]).unwrap();
+		s.read().unwrap().unwrap();
+		assert_eq!(
+			s.take_raw_stanza().unwrap(),
+			b"<close xmlns='urn:ietf:params:xml:ns:xmpp-framing'/>".to_vec(),
+		);
+	}
+
+	#[test]
+	fn xmppstream_reset_expects_a_fresh_stream_header() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"[..];
+		let mut s = XMPPStream::new(StreamConfig::c2s());
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		s.read().unwrap().unwrap();
+
+		s.reset();
+
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='after-tls'/>"[..];
+		s.feed(data).unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Opened{..} => (),
+			other => panic!("unexpected event: {:?}", other),
+		}
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => assert_eq!(st.root().attr.get("id").unwrap(), "after-tls"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_reset_clears_half_built_stanza_and_poison() {
+		let data = &b"<?xml version='1.0'?><stream:streamxmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='unfinished'>"[..];
+		let mut s = XMPPStream::new(StreamConfig::c2s());
+		s.feed(data).unwrap();
+		let e = s.read().err().unwrap();
+		match e {
+			Error::ParserError(_) => (),
+			other => panic!("unexpected error: {:?}", other),
+		}
+
+		s.reset();
+		assert_eq!(s.check_poison(), Ok(()));
+
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='fresh'/>"[..];
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => assert_eq!(st.root().attr.get("id").unwrap(), "fresh"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn xmppstream_reset_clears_pending_bytes() {
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='foobar2342' from='juliet@capulet.example' to='romeo@montague.example' type='get'>"[..];
+		let mut s = XMPPStream::new(StreamConfig::c2s());
+		s.stanza_limit = Some(4096);
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		assert!(s.pending_bytes() > 0);
+
+		s.reset();
+		assert_eq!(s.pending_bytes(), 0);
+
+		let data = &b"<?xml version='1.0'?><stream:stream xmlns:stream='http://etherx.jabber.org/streams' xmlns='jabber:client' version='1.0'><iq id='after-reset'/>"[..];
+		s.feed(data).unwrap();
+		s.read().unwrap().unwrap();
+		let ev = s.read().unwrap().unwrap();
+		match ev {
+			Event::Stanza(st) => assert_eq!(st.root().attr.get("id").unwrap(), "after-reset"),
+			other => panic!("unexpected event: {:?}", other),
+		}
+	}
 }